@@ -82,6 +82,11 @@ impl Animation {
         }
     }
 
+    /// Animations are only needed to redraw a window, a headless editor (without event sender) skips them
+    pub fn try_new(from: f32, to: f32, duration: f32, easing: EasingFunction, es: &Option<UserEventSender<EditorEvent>>) -> Option<Self> {
+        es.as_ref().map(|es| Self::new(from, to, duration, easing, es.clone()))
+    }
+
     pub fn new_infinite(from: f32, to: f32, duration: f32, easing: EasingFunction, es: UserEventSender<EditorEvent>) -> Self {
         let mut animation = Self::new(from, to, duration, easing, es);
         animation.infinite = true;
@@ -138,7 +143,7 @@ impl Animation {
         }
         self.last_t = t;
         self.value = self.from + (self.to - self.from) * (self.easing_fn)(t);
-        self.event_sender.send_event(EditorEvent::Redraw).unwrap();
+        let _ = self.event_sender.send_event(EditorEvent::Redraw); // the window may already be closed
     }
 
     #[inline]
@@ -152,6 +157,6 @@ impl Animation {
             self.last_t = 0.;
             self.is_ended = false;
         }
-        self.event_sender.send_event(EditorEvent::Redraw).unwrap();
+        let _ = self.event_sender.send_event(EditorEvent::Redraw); // the window may already be closed
     }
 }
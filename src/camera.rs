@@ -7,7 +7,6 @@ use speedy2d::Graphics2D;
 use speedy2d::window::UserEventSender;
 
 use crate::animation::{Animation, EasingFunction};
-use crate::cursor::Cursor;
 use crate::EditorEvent;
 
 #[derive(Clone)]
@@ -97,6 +96,16 @@ impl Camera {
         self.y = new_y;
     }
 
+    /// How far the camera is scrolled from the start of the document, where its animation ends
+    pub fn get_scroll(&self) -> Vector2<f32> { Vector2::new(self.x, self.y) }
+
+    pub fn scroll_to(&mut self, scroll: Vector2<f32>) {
+        let scroll = Vector2::new(scroll.x.max(0.), scroll.y.max(0.));
+        self.transition(scroll.x + self.initial_x, scroll.y + self.initial_y);
+        self.x = scroll.x;
+        self.y = scroll.y;
+    }
+
     pub fn computed_x(&self) -> f32 {
         if let Some(animation) = &self.animation.x { animation.value } else { self.x + self.initial_x }
     }
@@ -109,23 +118,25 @@ impl Camera {
         Vector2::new(self.computed_x(), self.computed_y())
     }
 
-    pub fn get_cursor_x_with_offset(&self, cursor: &Cursor) -> f32 {
-        cursor.real_x() + self.initial_x - cursor.font.borrow().char_width
+    /// The vertical bounds of the document seen through the camera, with a margin as large as its offset
+    pub fn get_visible_y_range(&self) -> (f32, f32) {
+        (self.computed_y(), self.computed_y() + self.height - self.initial_y)
+    }
+
+    pub fn get_cursor_x_with_offset(&self, cursor_x: f32, char_width: f32) -> f32 {
+        cursor_x + self.initial_x - char_width
     }
 
-    pub fn get_cursor_y_with_offset(&self, cursor: &Cursor) -> f32 {
-        cursor.real_y() + self.initial_y - cursor.font.borrow().char_height
+    pub fn get_cursor_y_with_offset(&self, cursor_y: f32, char_height: f32) -> f32 {
+        cursor_y + self.initial_y - char_height
     }
 
     fn transition(&mut self, x: f32, y: f32) {
         let start_x = if let Some(animation_x) = &self.animation.x { animation_x.value } else { self.computed_x() };
         let start_y = if let Some(animation_y) = &self.animation.y { animation_y.value } else { self.computed_y() };
         let duration = 100.;
-        let es = self.event_sender.clone().unwrap();
-        let new_animation_x = Animation::new(start_x, x, duration, EasingFunction::SmootherStep, es.clone());
-        let new_animation_y = Animation::new(start_y, y, duration, EasingFunction::SmootherStep, es);
-        self.animation.x = Some(new_animation_x);
-        self.animation.y = Some(new_animation_y);
+        self.animation.x = Animation::try_new(start_x, x, duration, EasingFunction::SmootherStep, &self.event_sender);
+        self.animation.y = Animation::try_new(start_y, y, duration, EasingFunction::SmootherStep, &self.event_sender);
     }
 
     pub fn _render(&self, graphics: &mut Graphics2D) {
@@ -17,6 +17,7 @@ use crate::font::Font;
 use crate::input::{Input, Validator};
 use crate::loader::Loader;
 use crate::render_helper::{draw_rectangle, draw_rounded_rectangle, draw_rounded_rectangle_with_border};
+use crate::event_sender::send_event;
 
 const ITEM_PADDING: f32 = 5.;
 const SEPARATOR_HEIGHT_RATIO: f32 = 1. / 10.;
@@ -110,9 +111,9 @@ impl ContextualMenu {
         }
     }
 
-    pub fn new_with_items(font: Rc<RefCell<Font>>, es: UserEventSender<EditorEvent>, items: Vec<MenuItem>) -> Self {
+    pub fn new_with_items(font: Rc<RefCell<Font>>, es: Option<UserEventSender<EditorEvent>>, items: Vec<MenuItem>) -> Self {
         let mut menu = Self::new(font);
-        menu.event_sender = Some(es);
+        menu.event_sender = es;
         menu.set_items(items);
         menu
     }
@@ -126,10 +127,8 @@ impl ContextualMenu {
         self.is_visible = true;
         let start_width = if let Some(animation_width) = &self.size_animation.x { animation_width.value } else { 0. };
         let start_height = if let Some(animation_height) = &self.size_animation.y { animation_height.value } else { 0. };
-        let new_animation_width = Animation::new(start_width, self.width(), ANIMATION_DURATION, EasingFunction::SmootherStep, self.event_sender.clone().unwrap());
-        let new_animation_height = Animation::new(start_height, self.height(), ANIMATION_DURATION, EasingFunction::SmootherStep, self.event_sender.clone().unwrap());
-        self.size_animation.x = Some(new_animation_width);
-        self.size_animation.y = Some(new_animation_height);
+        self.size_animation.x = Animation::try_new(start_width, self.width(), ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender);
+        self.size_animation.y = Animation::try_new(start_height, self.height(), ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender);
     }
 
     pub fn close(&mut self) {
@@ -147,10 +146,8 @@ impl ContextualMenu {
         self.is_visible = false;
         let start_width = if let Some(animation_width) = &self.size_animation.x { animation_width.value } else { self.width() };
         let start_height = if let Some(animation_height) = &self.size_animation.y { animation_height.value } else { self.height() };
-        let new_animation_width = Animation::new(start_width, 0., ANIMATION_DURATION, EasingFunction::SmootherStep, self.event_sender.clone().unwrap());
-        let new_animation_height = Animation::new(start_height, 0., ANIMATION_DURATION, EasingFunction::SmootherStep, self.event_sender.clone().unwrap());
-        self.size_animation.x = Some(new_animation_width);
-        self.size_animation.y = Some(new_animation_height);
+        self.size_animation.x = Animation::try_new(start_width, 0., ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender);
+        self.size_animation.y = Animation::try_new(start_height, 0., ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender);
     }
 
     pub fn handle_key(&mut self, keycode: VirtualKeyCode, modifiers: ModifiersState) {
@@ -164,7 +161,7 @@ impl ContextualMenu {
             VirtualKeyCode::Right => { if self.focus_item_has_submenu() { self.focus_submenu() } else { self.close() } },
             VirtualKeyCode::Left => self.unfocus(),
             VirtualKeyCode::Return => self.select(),
-            VirtualKeyCode::Escape => self.event_sender.as_ref().unwrap().send_event(EditorEvent::MenuItemSelected(MenuAction::CloseMenu)).unwrap(),
+            VirtualKeyCode::Escape => send_event(&self.event_sender, EditorEvent::MenuItemSelected(MenuAction::CloseMenu)),
             VirtualKeyCode::Tab => { if !modifiers.shift() { self.move_down() } else { self.move_up() } },
             VirtualKeyCode::LShift
             | VirtualKeyCode::RShift
@@ -180,7 +177,7 @@ impl ContextualMenu {
 
     pub fn send_key_to_input(&mut self, keycode: VirtualKeyCode, modifiers: ModifiersState) {
         if let Some(input) =  &mut self.get_focused_item().input {
-            input.editor.modifiers = modifiers;
+            input.editor.set_modifiers(modifiers);
             match keycode {
                 VirtualKeyCode::Up => self.move_up(),
                 VirtualKeyCode::Down => self.move_down(),
@@ -196,14 +193,14 @@ impl ContextualMenu {
         let index = self.focus_index as i32 - 1;
         let start_y = if let Some(animation) = &self.focus_y_animation { animation.value } else { self.get_item_offset_y(self.focus_index as usize) };
         self.set_focus(index as isize);
-        self.focus_y_animation = Some(Animation::new(start_y, self.get_item_offset_y(self.focus_index as usize), ANIMATION_DURATION, EasingFunction::EaseOut, self.event_sender.clone().unwrap()));
+        self.focus_y_animation = Animation::try_new(start_y, self.get_item_offset_y(self.focus_index as usize), ANIMATION_DURATION, EasingFunction::EaseOut, &self.event_sender);
     }
 
     fn move_down(&mut self) {
         if !self.is_focus() { return; }
         let start_y = if let Some(animation) = &self.focus_y_animation { animation.value } else { self.get_item_offset_y(self.focus_index as usize) };
         self.set_focus(self.focus_index + 1);
-        self.focus_y_animation = Some(Animation::new(start_y, self.get_item_offset_y(self.focus_index as usize), ANIMATION_DURATION, EasingFunction::EaseOut, self.event_sender.clone().unwrap()));
+        self.focus_y_animation = Animation::try_new(start_y, self.get_item_offset_y(self.focus_index as usize), ANIMATION_DURATION, EasingFunction::EaseOut, &self.event_sender);
     }
 
     fn set_focus(&mut self, index: isize) {
@@ -225,6 +222,7 @@ impl ContextualMenu {
                 MenuAction::SaveWithInput(path)
                 | MenuAction::NewFileWithInput(path)
                 | MenuAction::OpenWithInput(path) => { input.set_placeholder(path); input.set_validator(Validator::File) },
+                MenuAction::GoToWithInput => input.set_validator(Validator::GoTo),
                 _ => {}
             }
             input.focus();
@@ -267,13 +265,13 @@ impl ContextualMenu {
 
     pub fn focus(&mut self) {
         self.focus_index = 0;
-        self.event_sender.as_ref().unwrap().send_event(EditorEvent::Focus(FocusElement::Menu(self.id))).unwrap();
+        send_event(&self.event_sender, EditorEvent::Focus(FocusElement::Menu(self.id)));
         self.set_focus(0);
     }
 
     pub fn unfocus(&mut self) {
         self.focus_index = -1;
-        self.event_sender.as_ref().unwrap().send_event(EditorEvent::Focus(self.previous_focus)).unwrap();
+        send_event(&self.event_sender, EditorEvent::Focus(self.previous_focus));
         if self.previous_focus == FocusElement::Editor { self.internal_close(); }
     }
 
@@ -281,12 +279,12 @@ impl ContextualMenu {
         if let Some(sub_menu) = &mut self.items[self.focus_index as usize].sub_menu {
             sub_menu.focus();
         } else if let Some(_input) = &mut self.items[self.focus_index as usize].input {
-           self.event_sender.as_ref().unwrap().send_event(EditorEvent::Focus(FocusElement::MenuInput(self.id))).unwrap()
+           send_event(&self.event_sender, EditorEvent::Focus(FocusElement::MenuInput(self.id)))
         }
     }
 
     pub fn toggle_loader(&mut self) {
-        let es = self.event_sender.clone().unwrap();
+        let Some(es) = self.event_sender.clone() else { return; }; // the loader is only drawn in a window
         let item = self.get_focused_item();
         match item.loader {
             None => item.start_loader(es),
@@ -296,18 +294,19 @@ impl ContextualMenu {
 
     pub fn select(&mut self) {
         if !self.is_focus() { return; }
-        let es = self.event_sender.as_ref().unwrap().clone();
+        let es = self.event_sender.clone();
         let item =  self.get_focused_item();
         let action = item.action.clone();
-        es.send_event(EditorEvent::MenuItemSelected(action.clone())).unwrap();
+        send_event(&es, EditorEvent::MenuItemSelected(action.clone()));
         if MenuAction::is_async(&action) {
-            item.start_loader(es.clone());
-            es.send_event(EditorEvent::Redraw).unwrap();
+            if let Some(es) = es { item.start_loader(es); }
+            send_event(&self.event_sender, EditorEvent::Redraw);
             return;
         }
         match action {
             MenuAction::OpenSubMenu => self.focus_submenu(),
             MenuAction::Void => {},
+            _ if MenuAction::keeps_menu_open(&action) => {},
             _ => self.close()
         }
     }
@@ -332,7 +331,7 @@ impl ContextualMenu {
     }
 
     pub fn define_input(&mut self) {
-        let es = self.event_sender.clone().unwrap();
+        let es = self.event_sender.clone();
         let mut id = self.id;
         for item in &mut self.items {
             if let Some(sub_menu) = &mut item.sub_menu {
@@ -344,7 +343,9 @@ impl ContextualMenu {
                 if item.input.is_none() {
                     let action = MenuAction::get_fn(&item.action);
                     item.input = Some(Input::new(id, action, es.clone()));
-                    if item.action == MenuAction::FindAndJumpWithInput { item.input.as_mut().unwrap().set_intermediate_result() }
+                    if item.action == MenuAction::SearchWithInput || item.action == MenuAction::ReplacementWithInput {
+                        item.input.as_mut().unwrap().set_intermediate_result()
+                    }
                 } else {
                     item.input.as_mut().unwrap().menu_id = id;
                 }
@@ -368,8 +369,8 @@ impl ContextualMenu {
     pub fn async_callback(&mut self, choices: Vec<String>) {
         if !self.is_visible || !self.is_focus() { return }
         let font = self.system_font.clone();
-        let es = self.event_sender.as_ref().unwrap().clone();
-        let mut item = self.get_focused_item();
+        let es = self.event_sender.clone();
+        let item = self.get_focused_item();
         item.loader = None;
         item.sub_menu = Some(ContextualMenu::new_with_items(
             font,
@@ -378,7 +379,7 @@ impl ContextualMenu {
         ));
         item.sub_menu.as_mut().unwrap().open();
         item.sub_menu.as_mut().unwrap().set_focus(0);
-        es.send_event(EditorEvent::Redraw);
+        send_event(&es, EditorEvent::Redraw);
     }
 
     fn width(&self) -> f32 { self.formatted_items.iter().map(|ftb| ftb.width()).max_by(|x, y| x.abs().partial_cmp(&y.abs()).unwrap()).unwrap_or(0.) + 8. * ITEM_PADDING }
@@ -8,8 +8,10 @@ use speedy2d::window::UserEventSender;
 
 use crate::animation::{Animation, EasingFunction};
 use crate::camera::Camera;
+use crate::document::position::Position;
 use crate::EditorEvent;
 use crate::font::Font;
+use crate::line::Line;
 use crate::render_helper::draw_rounded_line;
 
 pub const CURSOR_WIDTH: f32 = 3.;
@@ -50,6 +52,11 @@ impl Cursor {
         self.y = y;
     }
 
+    /// Follow the `position` of a cursor of the document, the move is animated
+    pub fn sync(&mut self, position: Position) {
+        if position != Position::new(self.x, self.y) { self.move_to(position.x, position.y); }
+    }
+
     /// The cursor theorical position
     pub fn real_x(&self) -> f32 { self.x as f32 * self.font.borrow().char_width }
 
@@ -65,34 +72,34 @@ impl Cursor {
         if let Some(animation) = &self.animation.y { animation.value } else { self.real_y() }
     }
 
-    pub fn position(&self) -> Vector2<f32> {
-        Vector2::new(self.computed_x(), self.computed_y())
+    /// The cursor position computed by the animation, where the text of its `line` is displayed in the document
+    pub fn display_position(&self, line: &Line) -> Vector2<f32> {
+        line.to_visual_position(self.computed_x()) + Vector2::new(0., self.computed_y() - self.real_y())
     }
 
     fn transition(&mut self, x: u32, y: u32) {
         let start_x = if let Some(animation_x) = &self.animation.x { animation_x.value } else { self.computed_x() };
         let start_y = if let Some(animation_y) = &self.animation.y { animation_y.value } else { self.computed_y() };
         let duration = 100.;
-        let es = self.event_sender.clone().unwrap();
-        let new_animation_x = Animation::new(start_x, x as f32 * self.font.borrow().char_width, duration, EasingFunction::SmootherStep, es.clone());
-        let new_animation_y = Animation::new(start_y, y as f32 * self.font.borrow().char_height, duration, EasingFunction::SmootherStep, es);
-        self.animation.x = Some(new_animation_x);
-        self.animation.y = Some(new_animation_y);
+        self.animation.x = Animation::try_new(start_x, x as f32 * self.font.borrow().char_width, duration, EasingFunction::SmootherStep, &self.event_sender);
+        self.animation.y = Animation::try_new(start_y, y as f32 * self.font.borrow().char_height, duration, EasingFunction::SmootherStep, &self.event_sender);
     }
 
-    pub fn render(&self, camera: &Camera, graphics: &mut Graphics2D) {
+    /// Draw the cursor where the text of its `line` is displayed
+    pub fn render(&self, line: &Line, camera: &Camera, graphics: &mut Graphics2D) {
+        let position = self.display_position(line);
         match self.cursor_type {
             CursorType::Carret => draw_rounded_line(
-                self.computed_x() - camera.computed_x() + CURSOR_OFFSET_X,
-                self.computed_y() - camera.computed_y(),
+                position.x - camera.computed_x() + CURSOR_OFFSET_X,
+                position.y - camera.computed_y(),
                 CURSOR_WIDTH,
                 self.font.borrow().char_height,
                 Color::BLACK,
                 graphics
             ),
             CursorType::Cross => {
-                let x = -camera.computed_x() + self.computed_x() + self.font.borrow().char_width / 2.;
-                let y = -camera.computed_y() + self.computed_y() + self.font.borrow().char_height / 2.;
+                let x = -camera.computed_x() + position.x + self.font.borrow().char_width / 2.;
+                let y = -camera.computed_y() + position.y + self.font.borrow().char_height / 2.;
                 graphics.draw_line(Vector2::new(x, 0.),Vector2::new(x, self.font.borrow().editor_size.y - camera.computed_y()), CURSOR_WIDTH/5., Color::BLACK);
                 graphics.draw_line(Vector2::new(0., y),Vector2::new(self.font.borrow().editor_size.x - camera.computed_x(), y), CURSOR_WIDTH/5., Color::BLACK);
             },
@@ -0,0 +1,155 @@
+use crate::document::position::Position;
+use crate::document::text_buffer::TextBuffer;
+
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+const QUOTES: [char; 2] = ['"', '`'];
+/// How far a matching bracket is looked for, so a lone bracket in a large file stays cheap to highlight
+const MAX_SCAN_LENGTH: usize = 100_000;
+
+/// Tells whether chars are between the quotes of their line, the quotes of a line are paired once for all its chars
+struct QuotedSpans<'a> {
+    buffer: &'a TextBuffer,
+    line: Option<(u32, Vec<(usize, usize)>)>, // the last line looked at and its pairs of quotes
+}
+
+impl<'a> QuotedSpans<'a> {
+    fn new(buffer: &'a TextBuffer) -> Self {
+        Self { buffer, line: Option::None }
+    }
+
+    /// Whether the char at `index` is between a pair of quotes, the brackets there are ignored, ex: the `(` of `"(x"`
+    fn contains(&mut self, index: usize) -> bool {
+        let y = self.buffer.get_position(index).y;
+        if self.line.as_ref().is_none_or(|(line, _)| *line != y) {
+            let quotes = QUOTES.iter().flat_map(|quote| get_quote_pairs(self.buffer, y, *quote)).collect();
+            self.line = Some((y, quotes));
+        }
+        self.line.as_ref().unwrap().1.iter().any(|(open, close)| *open < index && index < *close)
+    }
+}
+
+/// The char index of the closing bracket matching the opening one at `from`, the other kinds of brackets are ignored
+fn find_closing(buffer: &TextBuffer, from: usize, (open, close): (char, char)) -> Option<usize> {
+    let mut quoted = QuotedSpans::new(buffer);
+    let mut depth = 0;
+    for (i, c) in buffer.chars_at(from + 1).take(MAX_SCAN_LENGTH).enumerate() {
+        if (c == open || c == close) && quoted.contains(from + 1 + i) { continue; }
+        if c == open { depth += 1; }
+        else if c == close {
+            if depth == 0 { return Some(from + 1 + i); }
+            depth -= 1;
+        }
+    }
+    Option::None
+}
+
+/// The char index of the opening bracket matching the closing one at `from`
+fn find_opening(buffer: &TextBuffer, from: usize, (open, close): (char, char)) -> Option<usize> {
+    let mut chars = buffer.chars_at(from);
+    let mut quoted = QuotedSpans::new(buffer);
+    let mut depth = 0;
+    for i in (from.saturating_sub(MAX_SCAN_LENGTH) .. from).rev() {
+        let c = chars.prev()?;
+        if (c == open || c == close) && quoted.contains(i) { continue; }
+        if c == close { depth += 1; }
+        else if c == open {
+            if depth == 0 { return Some(i); }
+            depth -= 1;
+        }
+    }
+    Option::None
+}
+
+/// The pairs of quotes of the line `y`, in char indices of the text
+/// Quotes can't be nested so they are paired from the start of the line, an escaped quote is skipped
+fn get_quote_pairs(buffer: &TextBuffer, y: u32, quote: char) -> Vec<(usize, usize)> {
+    let line_start = buffer.get_char_index(Position::new(0, y));
+    let mut quotes = vec![];
+    let mut previous = ' ';
+    for (i, c) in buffer.line_text(y).chars().enumerate() {
+        if c == quote && previous != '\\' { quotes.push(line_start + i); }
+        previous = c;
+    }
+    quotes.chunks_exact(2).map(|pair| (pair[0], pair[1])).collect()
+}
+
+/// The char index of the bracket or quote matching the one at `index`, a bracket between quotes has no match
+fn find_match(buffer: &TextBuffer, index: usize) -> Option<usize> {
+    let c = buffer.char_at(index)?;
+    if BRACKETS.iter().any(|(open, close)| *open == c || *close == c) && QuotedSpans::new(buffer).contains(index) { return Option::None; }
+    if let Some(&pair) = BRACKETS.iter().find(|(open, _)| *open == c) { return find_closing(buffer, index, pair); }
+    if let Some(&pair) = BRACKETS.iter().find(|(_, close)| *close == c) { return find_opening(buffer, index, pair); }
+    if !QUOTES.contains(&c) { return Option::None; }
+    get_quote_pairs(buffer, buffer.get_position(index).y, c).into_iter().find_map(|(open, close)| {
+        if open == index { Some(close) } else if close == index { Some(open) } else { Option::None }
+    })
+}
+
+/// The bracket or quote right after `cursor`, else right before it, and the one matching it
+pub fn find_bracket_pair(buffer: &TextBuffer, cursor: Position) -> Option<(Position, Position)> {
+    let index = buffer.get_char_index(cursor);
+    [Some(index), index.checked_sub(1)].into_iter().flatten()
+        .find_map(|index| find_match(buffer, index).map(|other| (buffer.get_position(index), buffer.get_position(other))))
+}
+
+/// The innermost brackets or quotes around `cursor`, the quotes are only looked for in its line
+pub fn find_enclosing_pair(buffer: &TextBuffer, cursor: Position) -> Option<(Position, Position)> {
+    let index = buffer.get_char_index(cursor);
+    let mut depths = [0; BRACKETS.len()];
+    let mut chars = buffer.chars_at(index);
+    let mut quoted = QuotedSpans::new(buffer);
+    let mut brackets = Option::None;
+    for i in (index.saturating_sub(MAX_SCAN_LENGTH) .. index).rev() {
+        let Some(c) = chars.prev() else { break; };
+        if BRACKETS.iter().any(|(open, close)| *open == c || *close == c) && quoted.contains(i) { continue; }
+        if let Some(kind) = BRACKETS.iter().position(|(_, close)| *close == c) { depths[kind] += 1; }
+        else if let Some(kind) = BRACKETS.iter().position(|(open, _)| *open == c) {
+            if depths[kind] > 0 { depths[kind] -= 1; continue; }
+            brackets = find_closing(buffer, i, BRACKETS[kind]).map(|close| (i, close));
+            break;
+        }
+    }
+    let quotes = QUOTES.iter()
+        .flat_map(|quote| get_quote_pairs(buffer, cursor.y, *quote))
+        .filter(|(open, close)| *open < index && index <= *close)
+        .max_by_key(|(open, _)| *open);
+    let (open, close) = [brackets, quotes].into_iter().flatten().max_by_key(|(open, _)| *open)?;
+    Some((buffer.get_position(open), buffer.get_position(close)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::position::Position;
+    use crate::document::brackets::{find_bracket_pair, find_enclosing_pair};
+    use crate::document::text_buffer::TextBuffer;
+
+    #[test]
+    fn match_brackets() {
+        let buffer = TextBuffer::from("f(a, [b]) {\n    (c)\n}");
+        assert_eq!(find_bracket_pair(&buffer, Position::new(1, 0)), Some((Position::new(1, 0), Position::new(8, 0))));
+        assert_eq!(find_bracket_pair(&buffer, Position::new(9, 0)), Some((Position::new(8, 0), Position::new(1, 0)))); // before the cursor
+        assert_eq!(find_bracket_pair(&buffer, Position::new(10, 0)), Some((Position::new(10, 0), Position::new(0, 2)))); // across lines
+        assert_eq!(find_bracket_pair(&buffer, Position::new(3, 1)), Option::None);
+        assert_eq!(find_enclosing_pair(&buffer, Position::new(6, 0)), Some((Position::new(5, 0), Position::new(7, 0))));
+        assert_eq!(find_enclosing_pair(&buffer, Position::new(0, 1)), Some((Position::new(10, 0), Position::new(0, 2))));
+    }
+
+    #[test]
+    fn match_quotes() {
+        let buffer = TextBuffer::from(r#"a "b \" (c" "d""#);
+        assert_eq!(find_bracket_pair(&buffer, Position::new(2, 0)), Some((Position::new(2, 0), Position::new(10, 0))));
+        assert_eq!(find_bracket_pair(&buffer, Position::new(12, 0)), Some((Position::new(12, 0), Position::new(14, 0))));
+        assert_eq!(find_bracket_pair(&buffer, Position::new(8, 0)), Option::None); // the bracket has no match
+        assert_eq!(find_enclosing_pair(&buffer, Position::new(9, 0)), Some((Position::new(2, 0), Position::new(10, 0))));
+    }
+
+    #[test]
+    fn brackets_between_quotes_are_ignored() {
+        let buffer = TextBuffer::from("f(\")\", a)\n[\"]\"]");
+        assert_eq!(find_bracket_pair(&buffer, Position::new(1, 0)), Some((Position::new(1, 0), Position::new(8, 0))));
+        assert_eq!(find_bracket_pair(&buffer, Position::new(9, 0)), Some((Position::new(8, 0), Position::new(1, 0))));
+        assert_eq!(find_bracket_pair(&buffer, Position::new(3, 0)), Some((Position::new(2, 0), Position::new(4, 0)))); // the quote before the bracket of the string
+        assert_eq!(find_bracket_pair(&buffer, Position::new(0, 1)), Some((Position::new(0, 1), Position::new(4, 1))));
+        assert_eq!(find_enclosing_pair(&buffer, Position::new(7, 0)), Some((Position::new(1, 0), Position::new(8, 0))));
+    }
+}
@@ -0,0 +1,209 @@
+use std::fmt;
+
+const UTF8_BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: [u8; 2] = [0xFF, 0xFE];
+const UTF16_BE_BOM: [u8; 2] = [0xFE, 0xFF];
+/// The chars of the bytes 0x80 to 0x9F in Windows-1252, the other bytes are the same as in Latin-1
+/// The 5 undefined bytes are kept as the matching control chars so they round-trip
+const WINDOWS_1252_CHARS: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    Cr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252, // also used for Latin-1, which it extends
+}
+
+/// How a document is stored on the disk, the buffer always uses `\n` and UTF-8
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FileFormat {
+    pub encoding: Encoding,
+    pub bom: bool,
+    pub line_ending: LineEnding,
+    pub trailing_line_breaks: usize, // the line breaks at the end of the file, kept out of the buffer and written back
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+
+    /// The most used line ending of `text`, LF if it has a single line
+    fn detect(text: &str) -> Self {
+        let crlf_count = text.matches("\r\n").count();
+        let cr_count = text.matches('\r').count() - crlf_count;
+        let lf_count = text.matches('\n').count() - crlf_count;
+        if crlf_count > lf_count && crlf_count >= cr_count { LineEnding::Crlf }
+        else if cr_count > lf_count && cr_count > crlf_count { LineEnding::Cr }
+        else { LineEnding::Lf }
+    }
+}
+
+impl FileFormat {
+    /// Detect the format of the file content `bytes` and return its text with `\n` line breaks, without the ones at its end
+    pub fn decode(bytes: &[u8]) -> (String, Self) {
+        let (encoding, bom, content) = if let Some(content) = bytes.strip_prefix(&UTF8_BOM) {
+            (Encoding::Utf8, true, content)
+        } else if let Some(content) = bytes.strip_prefix(&UTF16_LE_BOM) {
+            (Encoding::Utf16Le, true, content)
+        } else if let Some(content) = bytes.strip_prefix(&UTF16_BE_BOM) {
+            (Encoding::Utf16Be, true, content)
+        } else if let Some(encoding) = guess_utf16(bytes) {
+            (encoding, false, bytes)
+        } else if std::str::from_utf8(bytes).is_ok() {
+            (Encoding::Utf8, false, bytes)
+        } else {
+            (Encoding::Windows1252, false, bytes)
+        };
+        let text = match encoding {
+            Encoding::Utf8 => String::from_utf8_lossy(content).into_owned(),
+            Encoding::Utf16Le => decode_utf16(content, u16::from_le_bytes),
+            Encoding::Utf16Be => decode_utf16(content, u16::from_be_bytes),
+            Encoding::Windows1252 => content.iter().map(|byte| match byte {
+                0x80 ..= 0x9F => WINDOWS_1252_CHARS[(byte - 0x80) as usize],
+                _ => *byte as char,
+            }).collect(),
+        };
+        let line_ending = LineEnding::detect(&text);
+        let mut text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let text_len = text.trim_end_matches('\n').len();
+        let trailing_line_breaks = text.len() - text_len;
+        text.truncate(text_len);
+        (text, Self { encoding, bom, line_ending, trailing_line_breaks })
+    }
+
+    /// The content of a file in this format for `text`, the chars that Windows-1252 can't store are written as `?`
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let text = text.to_string() + &"\n".repeat(self.trailing_line_breaks);
+        let text = if self.line_ending == LineEnding::Lf { text } else { text.replace('\n', self.line_ending.as_str()) };
+        let mut bytes = vec![];
+        match self.encoding {
+            Encoding::Utf8 => {
+                if self.bom { bytes.extend(UTF8_BOM); }
+                bytes.extend(text.as_bytes());
+            },
+            Encoding::Utf16Le => {
+                if self.bom { bytes.extend(UTF16_LE_BOM); }
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            },
+            Encoding::Utf16Be => {
+                if self.bom { bytes.extend(UTF16_BE_BOM); }
+                bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            },
+            Encoding::Windows1252 => bytes.extend(text.chars().map(|c| match c as u32 {
+                0x80 ..= 0x9F if !WINDOWS_1252_CHARS.contains(&c) => b'?',
+                code @ 0 ..= 0xFF => code as u8,
+                _ => WINDOWS_1252_CHARS.iter().position(|&other| other == c).map_or(b'?', |i| 0x80 + i as u8),
+            })),
+        }
+        bytes
+    }
+}
+
+/// Whether the decoded content of a file is text and not binary data, which holds ASCII control chars other than whitespace
+pub fn is_text(text: &str) -> bool {
+    !text.chars().any(|c| c.is_ascii_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b'))
+}
+
+/// Text without a BOM is read as UTF-16 when most of its even or odd bytes are null, like ASCII chars encoded in UTF-16
+fn guess_utf16(bytes: &[u8]) -> Option<Encoding> {
+    if bytes.len() < 2 || !bytes.len().is_multiple_of(2) { return Option::None; }
+    let pair_count = bytes.len() / 2;
+    let even_nulls = bytes.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd_nulls = bytes.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+    if odd_nulls * 2 > pair_count && even_nulls == 0 { Some(Encoding::Utf16Le) }
+    else if even_nulls * 2 > pair_count && odd_nulls == 0 { Some(Encoding::Utf16Be) }
+    else { Option::None }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self { LineEnding::Lf => "LF", LineEnding::Crlf => "CRLF", LineEnding::Cr => "CR" };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Windows1252 => "Windows-1252",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}, {}", self.encoding, if self.bom { " with BOM" } else { "" }, self.line_ending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::file_format::{is_text, Encoding, FileFormat, LineEnding};
+
+    fn round_trip(bytes: &[u8]) -> (String, FileFormat) {
+        let (text, format) = FileFormat::decode(bytes);
+        assert_eq!(format.encode(&text), bytes);
+        (text, format)
+    }
+
+    #[test]
+    fn line_endings_round_trip() {
+        assert_eq!(round_trip(b"a\nb"), ("a\nb".to_string(), FileFormat::default()));
+        let (text, format) = round_trip(b"a\r\nb\r\n");
+        assert_eq!(text, "a\nb"); // the line break at the end is written back
+        assert_eq!((format.line_ending, format.trailing_line_breaks), (LineEnding::Crlf, 1));
+        assert_eq!(round_trip(b"a\n\n\n").1.trailing_line_breaks, 3);
+        assert_eq!(round_trip(b"a\rb").1.line_ending, LineEnding::Cr);
+    }
+
+    #[test]
+    fn encodings_round_trip() {
+        let (text, format) = round_trip(b"\xEF\xBB\xBFcaf\xC3\xA9");
+        assert_eq!(text, "café");
+        assert_eq!((format.encoding, format.bom), (Encoding::Utf8, true));
+        let (text, format) = round_trip(b"\xFF\xFEa\x00\xE9\x00\r\x00\n\x00");
+        assert_eq!(text, "aé");
+        assert_eq!((format.encoding, format.bom, format.line_ending), (Encoding::Utf16Le, true, LineEnding::Crlf));
+        assert_eq!(round_trip(b"\x00h\x00i").1.encoding, Encoding::Utf16Be);
+        let (text, format) = round_trip(b"caf\xE9 \x80\x81");
+        assert_eq!(text, "café \u{20AC}\u{81}");
+        assert_eq!(format.encoding, Encoding::Windows1252);
+        assert_eq!(format.encode("\u{1F44D}"), b"?");
+    }
+
+    #[test]
+    fn detect_binary_files() {
+        assert!(is_text(&FileFormat::decode(b"a\tb\r\n\x1b[1m").0));
+        assert!(is_text(&FileFormat::decode(b"\xFF\xFEa\x00").0)); // the null bytes belong to UTF-16 chars
+        assert!(!is_text(&FileFormat::decode(b"\x89PNG\r\n\x1a\n\x00\x00").0));
+    }
+}
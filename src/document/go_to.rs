@@ -0,0 +1,70 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::document::position::Position;
+
+lazy_static! {
+    static ref GO_TO_REGEX: Regex = Regex::new(r"^\s*(?:([+-])(\d+)|(\d+)(?::(\d+))?)\s*$").unwrap();
+    static ref FILE_POSITION_REGEX: Regex = Regex::new(r"^(.+?):(\d+(?::\d+)?)$").unwrap();
+}
+
+/// A target of the go-to command, the lines and columns are counted from 1 as they are shown to the user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GoTo {
+    Line(u32, Option<u32>), // `line` or `line:col`
+    Relative(i32), // `+N` or `-N` lines from the cursor
+}
+
+impl GoTo {
+    /// `None` if `text` is not a valid target, the line and column 0 don't exist
+    pub fn parse(text: &str) -> Option<Self> {
+        let captures = GO_TO_REGEX.captures(text)?;
+        if let Some(sign) = captures.get(1) {
+            let count: i32 = captures[2].parse().ok()?;
+            return Some(GoTo::Relative(if sign.as_str() == "-" { -count } else { count }));
+        }
+        let line: u32 = captures[3].parse().ok()?;
+        let column: Option<u32> = match captures.get(4) { Some(column) => Some(column.as_str().parse().ok()?), None => Option::None };
+        if line == 0 || column == Some(0) { return Option::None; }
+        Some(GoTo::Line(line, column))
+    }
+
+    /// The position of the target in the buffer, a relative move keeps the column of the cursor
+    /// The position is not clamped to the document
+    pub fn get_position(&self, cursor: Position) -> Position {
+        match *self {
+            GoTo::Line(line, column) => Position::new(column.unwrap_or(1) - 1, line - 1),
+            GoTo::Relative(delta) => Position::new(cursor.x, cursor.y.saturating_add_signed(delta)),
+        }
+    }
+}
+
+/// Split a path from the command line as `notes.txt:120:5` into the file path and its target
+pub fn split_file_position(arg: &str) -> (&str, Option<GoTo>) {
+    let Some(captures) = FILE_POSITION_REGEX.captures(arg) else { return (arg, Option::None); };
+    match GoTo::parse(captures.get(2).unwrap().as_str()) {
+        Some(target) => (captures.get(1).unwrap().as_str(), Some(target)),
+        None => (arg, Option::None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::position::Position;
+    use crate::document::go_to::{GoTo, split_file_position};
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(GoTo::parse("120"), Some(GoTo::Line(120, Option::None)));
+        assert_eq!(GoTo::parse(" 12:5 "), Some(GoTo::Line(12, Some(5))));
+        assert_eq!(GoTo::parse("-3"), Some(GoTo::Relative(-3)));
+        assert_eq!(GoTo::parse("+3:2"), Option::None);
+        assert_eq!(GoTo::parse("0"), Option::None);
+        assert_eq!(GoTo::parse("a"), Option::None);
+        assert_eq!(GoTo::Relative(-3).get_position(Position::new(4, 1)), Position::new(4, 0));
+        assert_eq!(GoTo::Line(12, Some(5)).get_position(Position::new(4, 1)), Position::new(4, 11));
+        assert_eq!(split_file_position("notes.txt:120:5"), ("notes.txt", Some(GoTo::Line(120, Some(5)))));
+        assert_eq!(split_file_position("notes.txt"), ("notes.txt", Option::None));
+        assert_eq!(split_file_position("C:notes.txt"), ("C:notes.txt", Option::None));
+    }
+}
@@ -0,0 +1,136 @@
+use std::time::{Duration, Instant};
+
+use crate::document::Alignment;
+use crate::document::position::Position;
+use crate::document::range::Range;
+use crate::document::style_range::StyleRange;
+use crate::document::text_buffer::TextBuffer;
+
+const MAX_HISTORY_SIZE: usize = 500;
+const GROUP_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// The kind of mutation about to be applied, used to group edits into undo units
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum EditKind {
+    Insert { is_whitespace: bool },
+    Delete,
+    Other,
+}
+
+/// A snapshot of everything an undo must restore
+#[derive(Debug, Clone)]
+pub struct HistoryState {
+    pub buffer: TextBuffer, // cloning a rope is cheap, both copies share their unchanged nodes
+    pub alignments: Vec<(usize, Alignment)>, // lines that are not aligned to the left
+    pub style_buffer: Vec<StyleRange>,
+    pub cursor: Position,
+    pub selection: Range,
+    pub secondary_cursors: Vec<(Position, Range)>,
+    pub revision: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LastEdit {
+    kind: EditKind,
+    position: Position,
+    timestamp: Instant,
+}
+
+pub struct History {
+    undo_stack: Vec<HistoryState>,
+    redo_stack: Vec<HistoryState>,
+    last_edit: Option<LastEdit>,
+    lock_depth: u32,
+    next_revision: usize,
+    pub revision: usize,       // revision of the document currently displayed
+    pub saved_revision: usize, // revision of the document on disk
+}
+
+impl Default for History {
+    fn default() -> Self { Self::new() }
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo_stack: vec![],
+            redo_stack: vec![],
+            last_edit: Option::None,
+            lock_depth: 0,
+            next_revision: 1,
+            revision: 0,
+            saved_revision: 0,
+        }
+    }
+
+    /// Forget every undo unit, ex: when a new file is loaded
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Prevent nested mutations (ex: `delete_selection` called by `add_char`) from creating their own undo unit
+    pub fn lock(&mut self) { self.lock_depth += 1; }
+
+    pub fn unlock(&mut self) { self.lock_depth = self.lock_depth.saturating_sub(1); }
+
+    pub fn is_locked(&self) -> bool { self.lock_depth > 0 }
+
+    /// The next edit will always open a new undo unit
+    pub fn break_group(&mut self) { self.last_edit = Option::None; }
+
+    pub fn is_saved(&self) -> bool { self.revision == self.saved_revision }
+
+    pub fn mark_saved(&mut self) {
+        self.saved_revision = self.revision;
+        self.break_group();
+    }
+
+    /// Check if an edit of `kind` starting at `position` continues the previous undo unit
+    fn extends_last_edit(&self, kind: EditKind, position: Position) -> bool {
+        let last = match &self.last_edit { Some(last) => last, None => return false };
+        if last.timestamp.elapsed() > GROUP_TIMEOUT || last.position.y != position.y { return false; }
+        match (last.kind, kind) {
+            // A word and the spaces that follow it are a single unit, a new word begins a new one
+            (EditKind::Insert { is_whitespace: was_whitespace }, EditKind::Insert { is_whitespace }) =>
+                position.x == last.position.x + 1 && (!was_whitespace || is_whitespace),
+            (EditKind::Delete, EditKind::Delete) => position.x + 1 == last.position.x,
+            _ => false,
+        }
+    }
+
+    /// Register an edit of `kind` at `position`
+    /// Return true if it opens a new undo unit, false if it is merged into the current one
+    pub fn record_edit(&mut self, kind: EditKind, position: Position) -> bool {
+        if self.is_locked() { return false; }
+        let is_merged = self.extends_last_edit(kind, position);
+        self.last_edit = if kind == EditKind::Other { Option::None } else { Some(LastEdit { kind, position, timestamp: Instant::now() }) };
+        self.redo_stack.clear();
+        !is_merged
+    }
+
+    /// Store the state of the document right before the edit that opened a new undo unit
+    pub fn push(&mut self, state: HistoryState) {
+        self.undo_stack.push(state);
+        if self.undo_stack.len() > MAX_HISTORY_SIZE { self.undo_stack.remove(0); }
+        self.revision = self.next_revision;
+        self.next_revision += 1;
+    }
+
+    /// Swap the `current` state with the last undo unit
+    pub fn undo(&mut self, current: HistoryState) -> Option<HistoryState> {
+        let state = self.undo_stack.pop()?;
+        self.redo_stack.push(current);
+        self.revision = state.revision;
+        self.break_group();
+        Some(state)
+    }
+
+    /// Swap the `current` state with the last undone unit
+    pub fn redo(&mut self, current: HistoryState) -> Option<HistoryState> {
+        let state = self.redo_stack.pop()?;
+        self.undo_stack.push(current);
+        self.revision = state.revision;
+        self.break_group();
+        Some(state)
+    }
+}
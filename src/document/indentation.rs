@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+const MAX_INDENT_WIDTH: u32 = 8;
+
+/// How a level of indentation is written in the document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Indentation {
+    pub use_tabs: bool,
+    pub width: u32, // the number of spaces of a level, or the width of a tab
+}
+
+impl Default for Indentation {
+    fn default() -> Self { Self { use_tabs: false, width: 4 } }
+}
+
+impl Indentation {
+    /// The text inserted for a level of indentation
+    pub fn unit(&self) -> String {
+        if self.use_tabs { "\t".to_string() } else { " ".repeat(self.width as usize) }
+    }
+
+    /// Guess the indentation used by `text` from its indented lines, the tab width can't be guessed so it comes from `default`
+    pub fn detect(text: &str, default: Self) -> Self {
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut widths: HashMap<u32, u32> = HashMap::new(); // how often the indentation changes by n spaces between two lines
+        let mut previous_width = 0;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if line.starts_with('\t') { tab_lines += 1; continue; }
+            let width = (line.len() - line.trim_start_matches(' ').len()) as u32;
+            if width > 0 { space_lines += 1; }
+            let delta = width.abs_diff(previous_width);
+            if delta > 1 && delta <= MAX_INDENT_WIDTH { *widths.entry(delta).or_insert(0) += 1; }
+            previous_width = width;
+        }
+        if tab_lines == 0 && space_lines == 0 { return default; }
+        if tab_lines > space_lines { return Self { use_tabs: true, width: default.width }; }
+        // The most frequent change, the smallest one in case of a tie
+        let width = widths.iter().max_by_key(|(width, count)| (**count, std::cmp::Reverse(**width))).map(|(width, _)| *width);
+        Self { use_tabs: false, width: width.unwrap_or(default.width) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::indentation::Indentation;
+
+    #[test]
+    fn detect_indentation() {
+        let default = Indentation { use_tabs: false, width: 4 };
+        assert_eq!(Indentation::detect("no indentation", default), default);
+        assert_eq!(Indentation::detect("a\n\tb\n\t\tc", default), Indentation { use_tabs: true, width: 4 });
+        assert_eq!(Indentation::detect("a\n  b\n    c\n  d\ne", default), Indentation { use_tabs: false, width: 2 });
+        assert_eq!(Indentation::detect("- a\n        b", default), Indentation { use_tabs: false, width: 8 });
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref LIST_ITEM_REGEX: Regex = Regex::new(r"^([ \t]*)(?:([-*+])|(\d{1,9})([.)])|([a-z])([.)]))( +)(\[[ xX]\] +)?").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListMarker {
+    Bullet(char), // `-`, `*` or `+`
+    Number(u32, char), // `1.` or `1)`
+    Letter(char, char), // `a.` or `a)`
+}
+
+impl ListMarker {
+    pub fn is_ordered(&self) -> bool { !matches!(self, ListMarker::Bullet(_)) }
+
+    /// The marker of the item after this one, the letters stop at `z`
+    pub fn next(&self) -> Self {
+        match *self {
+            ListMarker::Bullet(bullet) => ListMarker::Bullet(bullet),
+            ListMarker::Number(number, delimiter) => ListMarker::Number(number + 1, delimiter),
+            ListMarker::Letter(letter, delimiter) => ListMarker::Letter(if letter < 'z' { (letter as u8 + 1) as char } else { letter }, delimiter),
+        }
+    }
+
+    /// The marker of the first item of a list of the same kind
+    pub fn first(&self) -> Self {
+        match *self {
+            ListMarker::Bullet(bullet) => ListMarker::Bullet(bullet),
+            ListMarker::Number(_, delimiter) => ListMarker::Number(1, delimiter),
+            ListMarker::Letter(_, delimiter) => ListMarker::Letter('a', delimiter),
+        }
+    }
+
+    /// Whether the two markers are numbered the same way, so one can follow the other
+    pub fn is_same_kind(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ListMarker::Number(_, a), ListMarker::Number(_, b)) | (ListMarker::Letter(_, a), ListMarker::Letter(_, b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ListMarker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListMarker::Bullet(bullet) => write!(f, "{}", bullet),
+            ListMarker::Number(number, delimiter) => write!(f, "{}{}", number, delimiter),
+            ListMarker::Letter(letter, delimiter) => write!(f, "{}{}", letter, delimiter),
+        }
+    }
+}
+
+/// The start of a line of a list, ex: `  2) ` or `- [x] `
+/// It is made of ASCII chars so its lengths in bytes and in graphemes are the same
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    pub indent: String,
+    pub marker: ListMarker,
+    pub spacing: String, // the spaces after the marker, kept for the next items
+    pub has_checkbox: bool,
+    pub len: usize, // the length of the start of the line, the content of the item comes after
+}
+
+impl ListItem {
+    pub fn parse(line: &str) -> Option<Self> {
+        let captures = LIST_ITEM_REGEX.captures(line)?;
+        let marker = if let Some(bullet) = captures.get(2) {
+            ListMarker::Bullet(bullet.as_str().chars().next()?)
+        } else if let Some(number) = captures.get(3) {
+            ListMarker::Number(number.as_str().parse().ok()?, captures[4].chars().next()?)
+        } else {
+            ListMarker::Letter(captures[5].chars().next()?, captures[6].chars().next()?)
+        };
+        Some(Self {
+            indent: captures[1].to_string(),
+            marker,
+            spacing: captures[7].to_string(),
+            has_checkbox: captures.get(8).is_some(),
+            len: captures[0].len(),
+        })
+    }
+
+    /// The start of the line of the next item, with an unchecked box for a task
+    pub fn get_next_start(&self) -> String {
+        format!("{}{}{}{}", self.indent, self.marker.next(), self.spacing, if self.has_checkbox { "[ ] " } else { "" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::list::{ListItem, ListMarker};
+
+    #[test]
+    fn parse_list_items() {
+        let item = ListItem::parse("  9) text").unwrap();
+        assert_eq!((item.indent.as_str(), item.marker, item.len), ("  ", ListMarker::Number(9, ')'), 5));
+        assert_eq!(item.get_next_start(), "  10) ");
+        assert_eq!(ListItem::parse("-   [x] done").unwrap().get_next_start(), "-   [ ] ");
+        assert_eq!(ListItem::parse("b. text").unwrap().get_next_start(), "c. ");
+        assert_eq!(ListItem::parse("* ").unwrap().len, 2);
+        assert_eq!(ListItem::parse("e.g. text"), Option::None);
+        assert_eq!(ListItem::parse("-text"), Option::None);
+    }
+}
@@ -0,0 +1,1515 @@
+use std::{cmp, mem};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::document::brackets::{find_bracket_pair, find_enclosing_pair};
+use crate::document::file_format::FileFormat;
+use crate::document::go_to::GoTo;
+use crate::document::history::{EditKind, History, HistoryState};
+use crate::document::indentation::Indentation;
+use crate::document::list::{ListItem, ListMarker};
+use crate::document::multi_cursor::{get_bounds, merge, overlap, SecondaryCursor};
+use crate::document::position::Position;
+use crate::document::range::{is_before, Range, shift_after_insert, shift_after_remove, vector_max, vector_min};
+use crate::document::range_trait::RangeTrait;
+use crate::document::search::{Search, SearchOption};
+use crate::document::selection::Selection;
+use crate::document::style_range::{StyleRange, TextColor};
+use crate::document::text_buffer::TextBuffer;
+
+pub mod brackets;
+pub mod file_format;
+pub mod go_to;
+pub mod history;
+pub mod indentation;
+pub mod list;
+pub mod multi_cursor;
+pub mod position;
+pub mod range;
+pub mod range_trait;
+pub mod search;
+pub mod selection;
+pub mod style_range;
+pub mod text_buffer;
+
+/// The chars closed automatically when there is no preference for the type of the file
+pub const DEFAULT_AUTO_PAIRS: [&str; 4] = ["()", "[]", "{}", "\"\""];
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Alignment {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// The modifier keys held during an edit, ex: `alt` moves the cursor by words
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub fn shift(&self) -> bool { self.shift }
+
+    pub fn ctrl(&self) -> bool { self.ctrl }
+
+    pub fn alt(&self) -> bool { self.alt }
+
+    pub fn logo(&self) -> bool { self.logo }
+}
+
+/// An edit of the lines since the render layer last laid them out, it replays them on its own lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Insert(Position, Position), // the text inserted between the two positions
+    Remove(Position, Position), // the text that was between the two positions
+    SwapLines(u32, u32),
+    Restyle(u32, u32), // the styles or the alignment of the lines between the two changed
+    Reset(u32), // the whole text was replaced, by this number of lines
+}
+
+/// How the lines are split in rows on the screen, a soft wrapped line has several of them
+pub trait Layout {
+    /// The position displayed `rel_y` rows above or under `position`
+    fn get_position_on_row(&self, position: Position, rel_y: i32) -> Position;
+}
+
+/// A layout where every line is a single row, ex: for a document edited without a window
+pub struct PlainLayout;
+
+impl Layout for PlainLayout {
+    fn get_position_on_row(&self, position: Position, rel_y: i32) -> Position {
+        Position::new(position.x, cmp::max(position.y as i32 + rel_y, 0) as u32)
+    }
+}
+
+/// The text, its cursors, selections, styles and the history of its edits, without anything to display them
+/// An editing session can be scripted on it alone, the `Editor` lays it out and renders it
+pub struct Document {
+    pub buffer: TextBuffer,
+    pub cursor: Position, // the main cursor
+    pub selection: Selection,
+    pub secondary_cursors: Vec<SecondaryCursor>,
+    pub style_buffer: Vec<StyleRange>, // a buffer that keeps track of every style in the document
+    pub alignments: Vec<Alignment>, // the alignment of each line
+    pub modifiers: Modifiers,
+    pub indentation: Indentation,
+    pub file_format: FileFormat, // the encoding and line endings of the file, written back on save
+    pub search: Search,
+    search_matches: Option<Vec<(usize, usize)>>, // cached, in bytes of the text, `None` when they have to be found again
+    pub auto_pairs: Vec<(String, String)>, // the opening chars and the closing ones inserted with them
+    auto_closers: Vec<Position>, // the closing chars inserted automatically, that are stepped over when typed
+    pub history: History,
+    is_dirty: bool, // the document has changes that are not saved
+    changes: Vec<Change>, // taken by the render layer to lay out the changed lines
+}
+
+impl Default for Document {
+    fn default() -> Self { Self::new() }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Self {
+            buffer: TextBuffer::new(),
+            cursor: Position::ZERO,
+            selection: Selection::default(),
+            secondary_cursors: vec![],
+            style_buffer: vec![],
+            alignments: vec![Alignment::Left],
+            modifiers: Modifiers::default(),
+            indentation: Indentation::default(),
+            file_format: FileFormat::default(),
+            search: Search::default(),
+            search_matches: Option::None,
+            auto_pairs: parse_auto_pairs(&DEFAULT_AUTO_PAIRS),
+            auto_closers: vec![],
+            history: History::new(),
+            is_dirty: false,
+            changes: vec![],
+        }
+    }
+
+    /// The edits of the lines since the last call
+    pub fn take_changes(&mut self) -> Vec<Change> { mem::take(&mut self.changes) }
+
+    pub fn is_dirty(&self) -> bool { self.is_dirty }
+
+    pub fn set_dirty(&mut self, dirty: bool) { self.is_dirty = dirty; }
+
+    pub fn add_char(&mut self, c: &str) {
+        let kind = if self.selection.is_valid() { EditKind::Other } else { EditKind::Insert { is_whitespace: c.trim().is_empty() } };
+        self.push_history(kind);
+        self.history.lock();
+        self.for_each_cursor(|document| document.add_char_at_cursor(c));
+        self.history.unlock();
+    }
+
+    pub fn add_text(&mut self, text: &str) {
+        self.push_history(EditKind::Other);
+        self.history.lock();
+        for c in text.chars() {
+            self.add_char(&c.to_string());
+        }
+        self.history.unlock();
+    }
+
+    pub fn delete_char(&mut self) {
+        if self.secondary_cursors.is_empty() && !self.selection.is_valid() && self.cursor == Position::ZERO { return; } // Nothing to delete
+        let is_word_deletion = self.modifiers.alt() || self.modifiers.logo();
+        self.push_history(if self.selection.is_valid() || is_word_deletion { EditKind::Other } else { EditKind::Delete });
+        self.history.lock();
+        self.for_each_cursor(|document| document.delete_char_at_cursor());
+        self.history.unlock();
+    }
+
+    pub fn move_cursor(&mut self, position: Position) {
+        self.cursor = self.get_valid_cursor_position(position);
+    }
+
+    /// Move every cursor, the vertical moves go by rows of the `layout`
+    pub fn move_cursor_relative(&mut self, rel_x: i32, rel_y: i32, layout: &dyn Layout) {
+        // The lines are only switched around the main cursor
+        if self.modifiers.logo() && self.modifiers.ctrl() { return self.move_cursor_relative_at_cursor(rel_x, rel_y, layout); }
+        self.for_each_cursor(|document| document.move_cursor_relative_at_cursor(rel_x, rel_y, layout));
+    }
+
+    pub fn begin_selection(&mut self) {
+        self.selection.set_start(self.cursor);
+    }
+
+    pub fn end_selection(&mut self) {
+        self.selection.set_end(self.cursor);
+    }
+
+    pub fn delete_selection(&mut self) {
+        if self.selection.is_block() {
+            self.push_history(EditKind::Other); // the lines of the block are removed as a single undo unit
+            self.history.lock();
+            self.for_each_cursor(|document| document.delete_selection());
+            return self.history.unlock();
+        }
+        if self.selection.is_valid() {
+            self.push_history(EditKind::Other);
+            let selection_start = self.selection.start().unwrap();
+            self.remove_text(selection_start, self.selection.end().unwrap());
+            self.move_cursor(selection_start);
+            self.selection.reset();
+        }
+    }
+
+    pub fn get_valid_cursor_position(&self, position: Position) -> Position {
+        let max_y = self.buffer.line_count() - 1;
+        let y = cmp::min(position.y, max_y);
+        Position::new(cmp::min(position.x, self.buffer.line_len(y)), y)
+    }
+
+    pub fn select_current_word(&mut self) {
+        let (start, end) = self.buffer.get_word_at(self.cursor.x, self.cursor.y);
+        self.selection.set(Position::new(start, self.cursor.y), Position::new(end, self.cursor.y))
+    }
+
+    pub fn select_all(&mut self) {
+        self.remove_secondary_cursors();
+        let last_line_index = self.buffer.line_count() - 1;
+        self.selection.set(Position::ZERO, Position::new(self.buffer.line_len(last_line_index), last_line_index));
+    }
+
+    pub fn select_current_line(&mut self) {
+        let line_selection = Range::new(
+            Position::new(0, self.cursor.y),
+            Position::new(self.buffer.line_len(self.cursor.y), self.cursor.y)
+        );
+        self.selection.add(line_selection);
+        self.move_cursor(Position::new(0, self.cursor.y + 1))
+    }
+
+    /// The selected text of every cursor, in the document order and one per line, `None` without selection
+    pub fn copy(&self) -> Option<String> {
+        if !self.selection.is_valid() { return Option::None; }
+        if self.selection.is_block() { return Some(self.get_selected_text()); }
+        let mut bounds = self.get_cursors_bounds();
+        bounds.sort_by_key(|(start, _)| (start.y, start.x));
+        let selection_text = bounds.iter()
+            .filter(|(start, end)| start != end)
+            .map(|(start, end)| self.buffer.get_text_between(*start, *end))
+            .collect::<Vec<String>>()
+            .join("\n");
+        Some(selection_text)
+    }
+
+    /// Remove the selections of every cursor as a single undo unit, return their text as `copy` does
+    pub fn cut(&mut self) -> Option<String> {
+        let text = self.copy()?;
+        self.push_history(EditKind::Other);
+        self.history.lock();
+        self.for_each_cursor(|document| document.delete_selection());
+        self.history.unlock();
+        self.set_dirty(true);
+        Some(text)
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        if text.is_empty() { return; }
+        self.push_history(EditKind::Other);
+        self.history.lock();
+        let text = text.replace('\r', "");
+        let mut texts = vec![text.clone(); self.secondary_cursors.len() + 1];
+        // When there are as many lines as cursors (ex: after a multi cursor copy), each cursor gets its own line
+        let clipboard_lines: Vec<&str> = text.split('\n').collect();
+        if !self.secondary_cursors.is_empty() && clipboard_lines.len() == texts.len() {
+            let mut cursors_order: Vec<(usize, Position)> = self.get_cursors_bounds().iter().map(|(start, _)| *start).enumerate().collect();
+            cursors_order.sort_by_key(|(_, start)| (start.y, start.x));
+            for ((i, _), line) in cursors_order.iter().zip(clipboard_lines) { texts[*i] = line.to_string(); }
+        }
+        let mut texts = texts.into_iter();
+        self.for_each_cursor(|document| {
+            let text = texts.next().unwrap_or_default();
+            if document.selection.is_valid() { document.delete_selection(); }
+            document.cursor = document.insert_text(document.cursor, &text);
+        });
+        self.set_dirty(true);
+        self.history.unlock();
+    }
+
+    /// Insert `c` at the cursor currently in `self.cursor`, see `for_each_cursor`
+    fn add_char_at_cursor(&mut self, c: &str) {
+        let cursor = self.cursor;
+        if !self.selection.is_valid() && self.auto_closers.contains(&cursor) && self.buffer.grapheme_at(cursor).as_deref() == Some(c) {
+            self.auto_closers.retain(|closer| *closer != cursor);
+            return self.move_cursor(Position::new(cursor.x + 1, cursor.y)); // step over the closing char
+        }
+        let closer = self.get_auto_closer(c);
+        if closer.is_none() { self.delete_selection(); }
+        let pos = if self.selection.is_valid() { self.selection.start().unwrap() } else { cursor };
+        // A combining mark merges with the previous grapheme so the cursor does not always move by one column
+        let end = self.insert_text(pos, c);
+        self.set_dirty(true);
+        if let Some(closer) = closer {
+            if self.selection.is_valid() { // wrap the selection, which stays selected
+                let selection_end = shift_after_insert(self.selection.end().unwrap(), pos, end);
+                self.insert_text(selection_end, &closer);
+                self.selection.set(end, selection_end);
+                self.cursor = selection_end;
+                return;
+            }
+            self.insert_text(end, &closer);
+            self.auto_closers.push(end);
+        }
+        self.selection.reset();
+        self.cursor = end;
+    }
+
+    /// The closing chars inserted with `c`, `None` if `c` opens no pair or is a quote typed next to a word, ex: `l'eau`
+    fn get_auto_closer(&self, c: &str) -> Option<String> {
+        let (open, close) = self.auto_pairs.iter().find(|(open, _)| open == c)?;
+        if open == close && !self.selection.is_valid() {
+            let is_word = |grapheme: Option<String>| grapheme.is_some_and(|grapheme| grapheme.chars().all(char::is_alphanumeric));
+            let before = self.cursor.x.checked_sub(1).and_then(|x| self.buffer.grapheme_at(Position::new(x, self.cursor.y)));
+            if is_word(before) || is_word(self.buffer.grapheme_at(self.cursor)) { return Option::None; }
+        }
+        Some(close.clone())
+    }
+
+    /// Delete the char before the cursor currently in `self.cursor`, see `for_each_cursor`
+    fn delete_char_at_cursor(&mut self) {
+        if !self.selection.is_valid() && self.cursor == Position::ZERO { return; } // Nothing to delete
+        if self.modifiers.alt() || self.modifiers.logo() { // Delete the previous word
+            self.begin_selection();
+            self.move_cursor_relative_at_cursor(-1, 0, &PlainLayout);
+            self.end_selection();
+        }
+        self.set_dirty(true);
+        if self.selection.is_valid() {
+            self.delete_selection();
+            return;
+        }
+        let pos = self.cursor.x;
+        let row = self.cursor.y;
+        if pos == 0 {
+            let previous_line_length = self.buffer.line_len(row - 1);
+            self.remove_text(Position::new(previous_line_length, row - 1), Position::new(0, row));
+            self.cursor = Position::new(previous_line_length, row - 1);
+        } else {
+            assert!(pos <= self.buffer.line_len(row));
+            let previous_grapheme = self.buffer.grapheme_at(Position::new(pos - 1, row));
+            let next_grapheme = self.buffer.grapheme_at(Position::new(pos, row));
+            // Delete the closing char inserted with the deleted opening one - ex: ""
+            let is_auto_pair = self.auto_closers.contains(&Position::new(pos, row)) && self.auto_pairs.iter()
+                .any(|(open, close)| previous_grapheme.as_deref() == Some(open.as_str()) && next_grapheme.as_deref() == Some(close.as_str()));
+            let end = if is_auto_pair { pos + 1 } else { pos };
+            self.remove_text(Position::new(pos - 1, row), Position::new(end, row));
+            self.move_cursor_relative_at_cursor(-1, 0, &PlainLayout);
+        }
+        self.selection.reset();
+    }
+
+    /// Move the cursor currently in `self.cursor`, see `for_each_cursor`
+    fn move_cursor_relative_at_cursor(&mut self, rel_x: i32, rel_y: i32, layout: &dyn Layout) {
+        let max_y = self.buffer.line_count() as i32 - 1;
+        let mut new_x = self.cursor.x as i32 + rel_x;
+        let mut new_y = (self.cursor.y as i32 + rel_y).clamp(0, max_y);
+
+        if self.modifiers.shift() && self.selection.start().is_none() {
+            self.selection.set_start(self.cursor);
+        }
+
+        if self.modifiers.alt() {  // Move to the previous/next word
+            let (start, end) = self.buffer.get_next_jump(self.cursor.x, self.cursor.y);
+            if rel_x < 0 && start != self.cursor.x  {
+                new_x = start as i32;
+            } else if rel_x > 0 && end != self.cursor.x  {
+                new_x = end as i32;
+            }
+        } else if self.modifiers.logo() { // Move to the start/end of the line/file
+            if self.modifiers.ctrl() {
+                self.switch_lines(rel_y)
+            } else {
+                if rel_x < 0  { new_x = 0; }
+                else if rel_x > 0 { new_x = self.buffer.line_len(self.cursor.y) as i32; }
+                if rel_y < 0 { new_y = 0; }
+                else if rel_y > 0 { new_y = self.buffer.line_count() as i32 - 1; }
+            }
+        }
+
+        if self.selection.is_valid() && !self.modifiers.shift() && !(self.modifiers.logo() && self.modifiers.ctrl()) { // go to the start/end of the selection
+            if rel_x > 0 || rel_y > 0 {
+                self.move_cursor(self.selection.end().unwrap());
+                self.selection.reset();
+                return;
+            } else if rel_x < 0 || rel_y < 0 {
+                self.move_cursor(self.selection.start().unwrap());
+                self.selection.reset();
+                return;
+            }
+        }
+
+        if new_x < 0 {  // Go to line before
+            if self.cursor.y == 0 { return; }
+            let previous_line_buffer_size = self.buffer.line_len(self.cursor.y - 1);
+            self.cursor = Position::new(previous_line_buffer_size, self.cursor.y - 1);
+        } else if new_x as u32 > self.buffer.line_len(self.cursor.y) { // Go to line after
+            if self.cursor.y >= self.buffer.line_count() - 1 {return; }
+            self.cursor = Position::new(0, self.cursor.y + 1);
+        } else if rel_y != 0 && !self.modifiers.logo() {
+            // Move by visual row, a wrapped line has several of them
+            self.move_cursor(layout.get_position_on_row(self.cursor, rel_y));
+        } else {
+            // Classic move inside a line
+            // Check if x if inside new_y buffer limits
+            let new_buffer_len = self.buffer.line_len(new_y as u32) as i32;
+            self.cursor = Position::new(cmp::min(new_x, new_buffer_len) as u32, new_y as u32);
+        }
+        // Update selection
+        if self.modifiers.shift() {
+            self.selection.set_end(self.cursor);
+        } else if (rel_x.abs() > 0 || rel_y.abs() > 0) && self.selection.is_valid() && !(self.modifiers.logo() && self.modifiers.ctrl()) {
+            self.selection.reset();
+        }
+    }
+
+    /// Apply `action` to every cursor: each secondary cursor is swapped with the main one in turn
+    /// so `action` only has to deal with `self.cursor` and `self.selection`
+    fn for_each_cursor(&mut self, mut action: impl FnMut(&mut Self)) {
+        self.split_block_selection();
+        action(self);
+        if self.secondary_cursors.is_empty() { return; }
+        for i in 0 .. self.secondary_cursors.len() {
+            self.swap_cursor(i);
+            action(self);
+            self.swap_cursor(i);
+        }
+        self.merge_cursors();
+    }
+
+    fn swap_cursor(&mut self, index: usize) {
+        let secondary_cursor = &mut self.secondary_cursors[index];
+        mem::swap(&mut self.cursor, &mut secondary_cursor.cursor);
+        mem::swap(&mut self.selection, &mut secondary_cursor.selection);
+    }
+
+    /// The bounds of every cursor, beginning with the main one
+    fn get_cursors_bounds(&self) -> Vec<(Position, Position)> {
+        let mut bounds = vec![get_bounds(self.cursor, &self.selection)];
+        bounds.extend(self.secondary_cursors.iter().map(|c| c.bounds()));
+        bounds
+    }
+
+    /// Merge the cursors that overlap, the main cursor absorbs the secondary ones
+    fn merge_cursors(&mut self) {
+        let mut i = 0;
+        while i < self.secondary_cursors.len() {
+            if overlap(get_bounds(self.cursor, &self.selection), self.secondary_cursors[i].bounds()) {
+                let other = self.secondary_cursors.remove(i);
+                merge(&mut self.cursor, &mut self.selection, &other);
+                continue;
+            }
+            let mut j = i + 1;
+            while j < self.secondary_cursors.len() {
+                if overlap(self.secondary_cursors[i].bounds(), self.secondary_cursors[j].bounds()) {
+                    let other = self.secondary_cursors.remove(j);
+                    let secondary_cursor = &mut self.secondary_cursors[i];
+                    merge(&mut secondary_cursor.cursor, &mut secondary_cursor.selection, &other);
+                    j = i + 1; // the merged cursor may now overlap a previous one
+                } else {
+                    j += 1;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    /// Add a cursor at `position`, or remove the secondary cursor already there
+    pub fn add_cursor(&mut self, position: Position) {
+        if let Some(index) = self.secondary_cursors.iter().position(|c| c.cursor == position) {
+            self.secondary_cursors.remove(index);
+            return;
+        }
+        self.secondary_cursors.push(SecondaryCursor::new(position));
+        self.merge_cursors();
+    }
+
+    /// Add a cursor on the line above (`dir` < 0) or below (`dir` > 0) the outermost cursor
+    pub fn add_cursor_vertically(&mut self, dir: i32) {
+        let positions = self.get_cursors_bounds().iter().map(|(start, end)| if dir < 0 { *start } else { *end }).collect::<Vec<Position>>();
+        let origin = positions.into_iter().reduce(|a, b| if dir < 0 { vector_min(a, b) } else { vector_max(a, b) }).unwrap();
+        let y = origin.y as i32 + dir;
+        if y < 0 || y >= self.buffer.line_count() as i32 { return; }
+        self.add_cursor(Position::new(cmp::min(origin.x, self.buffer.line_len(y as u32)), y as u32));
+    }
+
+    /// Select the current word, or add a cursor on the next occurrence of the selected text
+    pub fn select_next_occurrence(&mut self) {
+        if !self.selection.is_valid() { return self.select_current_word(); }
+        let text = self.get_selected_text();
+        let last_cursor_end = self.secondary_cursors.last().map_or(self.selection.end().unwrap(), |c| c.bounds().1);
+        if let Some((start, end)) = self.buffer.find(&text, last_cursor_end) {
+            let mut secondary_cursor = SecondaryCursor::new(end);
+            secondary_cursor.selection.set(start, end);
+            self.secondary_cursors.push(secondary_cursor);
+            self.merge_cursors();
+        }
+    }
+
+    pub fn remove_secondary_cursors(&mut self) { self.secondary_cursors.clear(); }
+
+    /// Begin a block selection at `position`, ex: on alt + drag
+    pub fn begin_block_selection(&mut self, position: Position) {
+        self.remove_secondary_cursors();
+        self.selection.reset();
+        self.move_cursor(position);
+        self.begin_selection();
+        self.selection.set_block(true);
+    }
+
+    pub fn toggle_block_selection(&mut self) {
+        if !self.selection.is_valid() { return; }
+        self.remove_secondary_cursors();
+        let is_block = self.selection.is_block();
+        self.selection.set_block(!is_block);
+    }
+
+    /// Replace a block selection by one cursor per line, each one selecting the columns of the block on its line
+    fn split_block_selection(&mut self) {
+        if !self.selection.is_block() { return; }
+        let range = self.selection.get_range();
+        let (anchor, head) = (range.start.unwrap(), range.end.unwrap());
+        let top = cmp::min(anchor.y, head.y);
+        let lines_index = range.get_block_lines_index(&self.buffer);
+        self.selection.reset();
+        for (i, (start, end)) in lines_index.into_iter().enumerate() {
+            let y = top + i as u32;
+            let (anchor_x, head_x) = if head.x < anchor.x { (end, start) } else { (start, end) };
+            if y == head.y {
+                if start != end { self.selection.set(Position::new(anchor_x, y), Position::new(head_x, y)); }
+                self.cursor = Position::new(head_x, y);
+            } else {
+                let mut secondary_cursor = SecondaryCursor::new(Position::new(head_x, y));
+                if start != end { secondary_cursor.selection.set(Position::new(anchor_x, y), Position::new(head_x, y)); }
+                self.secondary_cursors.push(secondary_cursor);
+            }
+        }
+    }
+
+    pub fn get_selected_text(&self) -> String {
+        if !self.selection.is_valid() { return String::new() }
+        if self.selection.is_block() {
+            let top = self.selection.start().unwrap().y;
+            return self.selection.get_range().get_block_lines_index(&self.buffer).iter().enumerate()
+                .map(|(i, (start, end))| self.buffer.get_text_between(Position::new(*start, top + i as u32), Position::new(*end, top + i as u32)))
+                .collect::<Vec<String>>()
+                .join("\n");
+        }
+        self.buffer.get_text_between(self.selection.start().unwrap(), self.selection.end().unwrap())
+    }
+
+    /// Insert `text` at `position` in the buffer, the other cursors, the styles and the alignments follow it
+    /// Return the position right after the inserted text
+    pub fn insert_text(&mut self, position: Position, text: &str) -> Position {
+        let position = Position::new(cmp::min(position.x, self.buffer.line_len(position.y)), position.y);
+        let edited_bytes = self.get_lines_bytes(position.y, position.y);
+        let end = self.buffer.insert(position, text);
+        self.update_search_matches(edited_bytes, position.y, end.y);
+        for i in 0 .. end.y - position.y {
+            self.alignments.insert((position.y + i + 1) as usize, Alignment::Left);
+        }
+        for secondary_cursor in &mut self.secondary_cursors { secondary_cursor.shift(|p| shift_after_insert(p, position, end)); }
+        for closer in &mut self.auto_closers { *closer = shift_after_insert(*closer, position, end); }
+        for style_range in &mut self.style_buffer { style_range.shift_after_insert(position, end); }
+        if !text.is_empty() { self.changes.push(Change::Insert(position, end)); }
+        end
+    }
+
+    /// Remove the text between two positions in the buffer, the other cursors, the styles and the alignments follow it
+    pub fn remove_text(&mut self, start: Position, end: Position) {
+        let (start, end) = (vector_min(start, end), vector_max(start, end));
+        let edited_bytes = self.get_lines_bytes(start.y, end.y);
+        self.buffer.remove(start, end);
+        self.update_search_matches(edited_bytes, start.y, start.y);
+        self.alignments.drain((start.y + 1) as usize ..= end.y as usize);
+        for secondary_cursor in &mut self.secondary_cursors { secondary_cursor.shift(|p| shift_after_remove(p, start, end)); }
+        self.auto_closers.retain(|closer| is_before(*closer, start) || !is_before(*closer, end));
+        for closer in &mut self.auto_closers { *closer = shift_after_remove(*closer, start, end); }
+        for style_range in &mut self.style_buffer { style_range.shift_after_remove(start, end); }
+        self.style_buffer.retain(|style_range| style_range.is_valid());
+        self.changes.push(Change::Remove(start, end));
+    }
+
+    pub fn new_line(&mut self) {
+        self.push_history(EditKind::Other);
+        self.history.lock();
+        self.for_each_cursor(|document| document.new_line_at_cursor());
+        self.history.unlock();
+    }
+
+    fn new_line_at_cursor(&mut self) {
+        self.delete_selection();
+        let y = self.cursor.y;
+        let text = self.buffer.line_text(y);
+        // A list is continued when the line is split after the marker of its item
+        let item = ListItem::parse(&text).filter(|item| self.cursor.x as usize >= item.len);
+        if item.as_ref().is_some_and(|item| item.len == text.len()) { // an empty item ends the list
+            self.remove_text(Position::new(0, y), Position::new(self.buffer.line_len(y), y));
+            return self.move_cursor(Position::new(0, y));
+        }
+        self.insert_text(Position::new(self.cursor.x, y), "\n");
+        self.alignments[y as usize + 1] = self.alignments[y as usize]; // Preserve the alignement
+        let Some(item) = item else {
+            // Keep the indentation of the line, without the part after the cursor
+            let indent: String = text.chars().take_while(|c| *c == ' ' || *c == '\t').take(self.cursor.x as usize).collect();
+            self.insert_text(Position::new(0, y + 1), &indent);
+            return self.move_cursor(Position::new(indent.len() as u32, y + 1));
+        };
+        let start = item.get_next_start();
+        self.insert_text(Position::new(0, y + 1), &start);
+        self.move_cursor(Position::new(start.len() as u32, y + 1));
+        if item.marker.is_ordered() { self.renumber_list(y + 1); }
+    }
+
+    /// Number again the ordered items of the list around the line `y`, the first item of each level keeps its number
+    fn renumber_list(&mut self, y: u32) {
+        let mut start = y;
+        while start > 0 && ListItem::parse(&self.buffer.line_text(start - 1)).is_some() { start -= 1; }
+        let mut levels: Vec<(usize, ListMarker)> = vec![]; // the indentation and the marker of the last item of each level
+        for y in start .. self.buffer.line_count() {
+            let Some(item) = ListItem::parse(&self.buffer.line_text(y)) else { break; };
+            let indent = item.indent.len();
+            while levels.last().is_some_and(|(level_indent, _)| *level_indent > indent) { levels.pop(); }
+            let marker = match levels.last() {
+                Some((level_indent, previous)) if *level_indent == indent && previous.is_same_kind(&item.marker) => previous.next(),
+                _ => item.marker,
+            };
+            if marker != item.marker {
+                let marker_end = (indent + item.marker.to_string().len()) as u32;
+                self.replace_line_part(y, indent as u32, marker_end, &marker.to_string());
+            }
+            if levels.last().is_some_and(|(level_indent, _)| *level_indent == indent) { levels.pop(); }
+            levels.push((indent, marker));
+        }
+    }
+
+    /// Replace the columns `start_x .. end_x` of the line `y`, the cursor and the selection follow the text around them
+    fn replace_line_part(&mut self, y: u32, start_x: u32, end_x: u32, text: &str) {
+        let (start, end) = (Position::new(start_x, y), Position::new(end_x, y));
+        self.remove_text(start, end);
+        let new_end = self.insert_text(start, text);
+        let shift = |position| shift_after_insert(shift_after_remove(position, start, end), start, new_end);
+        self.cursor = shift(self.cursor);
+        if let (Some(selection_start), Some(selection_end)) = (self.selection.start(), self.selection.end()) {
+            self.selection.set(shift(selection_start), shift(selection_end));
+        }
+    }
+
+    /// Indent the lines selected by every cursor or its list item by a level, or outdent them, else insert an indentation at the cursor
+    pub fn indent_at_cursors(&mut self, outdent: bool) {
+        self.push_history(EditKind::Other);
+        self.history.lock();
+        self.for_each_cursor(|document| {
+            let y = document.cursor.y;
+            let is_multiline = document.selection.is_valid() && document.selection.start().unwrap().y != document.selection.end().unwrap().y;
+            if is_multiline { document.indent_selected_lines(outdent); }
+            else if ListItem::parse(&document.buffer.line_text(y)).is_some() { document.indent_list_item(y, outdent); }
+            else if outdent { document.indent_selected_lines(true); }
+            else { document.add_char_at_cursor(&document.indentation.unit()); }
+        });
+        self.history.unlock();
+    }
+
+    /// Indent or outdent the lines of every cursor, even without a selection
+    pub fn indent_lines_at_cursors(&mut self, outdent: bool) {
+        self.push_history(EditKind::Other);
+        self.history.lock();
+        self.for_each_cursor(|document| document.indent_selected_lines(outdent));
+        self.history.unlock();
+    }
+
+    /// Indent by a level every line of the selection, or the line of the cursor, or outdent them
+    /// The selection still covers the same text, a bound at the start of a line stays there
+    fn indent_selected_lines(&mut self, outdent: bool) {
+        let cursor = self.cursor;
+        let selection = self.selection.start().zip(self.selection.end()).filter(|_| self.selection.is_valid());
+        let (start, end) = selection.unwrap_or((cursor, cursor));
+        let last_y = if end.y > start.y && end.x == 0 { end.y - 1 } else { end.y }; // the line where the selection ends is not selected
+        let unit = self.indentation.unit();
+        for y in start.y ..= last_y {
+            let text = self.buffer.line_text(y);
+            if outdent {
+                let width = if text.starts_with('\t') { 1 } else { cmp::min(text.len() - text.trim_start_matches(' ').len(), self.indentation.width as usize) };
+                if width > 0 { self.replace_line_part(y, 0, width as u32, ""); }
+            } else if !text.is_empty() {
+                self.replace_line_part(y, 0, 0, &unit);
+            }
+        }
+        let keep_line_start = |before: Position, after: Position| if before.x == 0 { Position::new(0, after.y) } else { after };
+        self.cursor = keep_line_start(cursor, self.cursor);
+        if let (Some((start, end)), Some(new_start), Some(new_end)) = (selection, self.selection.start(), self.selection.end()) {
+            self.selection.set(keep_line_start(start, new_start), keep_line_start(end, new_end));
+        }
+    }
+
+    /// Move the list item of the line `y` to the next level, or to the previous one with `outdent`, and number its list again
+    fn indent_list_item(&mut self, y: u32, outdent: bool) {
+        let Some(item) = ListItem::parse(&self.buffer.line_text(y)) else { return; };
+        if outdent {
+            let width = if item.indent.starts_with('\t') { 1 } else { cmp::min(item.indent.len(), self.indentation.width as usize) };
+            if width == 0 { return; }
+            self.replace_line_part(y, 0, width as u32, "");
+        } else {
+            self.replace_line_part(y, 0, 0, &self.indentation.unit());
+            // The first item of a nested list starts from 1
+            let indent = item.indent.len() + self.indentation.unit().len();
+            let previous = y.checked_sub(1).and_then(|y| ListItem::parse(&self.buffer.line_text(y)));
+            if previous.is_none_or(|previous| previous.indent.len() < indent) && item.marker.is_ordered() {
+                let marker_end = (indent + item.marker.to_string().len()) as u32;
+                self.replace_line_part(y, indent as u32, marker_end, &item.marker.first().to_string());
+            }
+        }
+        self.renumber_list(y);
+    }
+
+    pub fn duplicate_line(&mut self) {
+        self.push_history(EditKind::Other);
+        let index_start = self.selection.start().unwrap_or(self.cursor).y as usize;
+        let index_end = self.selection.end().unwrap_or(self.cursor).y as usize;
+        let (block_start, block_end) = (Position::new(0, index_start as u32), Position::new(self.buffer.line_len(index_end as u32), index_end as u32));
+        let text = self.buffer.get_text_between(block_start, block_end) + "\n";
+        // The ranges crossing the bounds of the block are cut there, so they don't stretch over the copy once it is inserted
+        let block_lines: Vec<u32> = (index_start as u32 ..= index_end as u32).collect();
+        self.style_buffer = self.style_buffer.iter().flat_map(|style_range| style_range.split_lines(&block_lines, &self.buffer)).collect();
+        // The copy is inserted before the original lines, so the styles of the original end up right where the copy is
+        let copied_styles: Vec<StyleRange> = self.style_buffer.iter().filter_map(|style_range| style_range.intersect(block_start, block_end)).collect();
+        let block_alignments = self.alignments[index_start ..= index_end].to_vec();
+        self.insert_text(block_start, &text);
+        self.style_buffer.extend(copied_styles);
+        let nb_lines = index_end - index_start + 1;
+        for (i, alignment) in block_alignments.into_iter().enumerate() {
+            self.alignments[index_start + i] = alignment;
+            self.alignments[index_start + nb_lines + i] = alignment;
+        }
+        self.changes.push(Change::Restyle(index_start as u32, (index_end + nb_lines) as u32));
+        self.move_cursor(Position::new(self.cursor.x, self.cursor.y + nb_lines as u32))
+    }
+
+    pub fn switch_lines(&mut self, dir: i32) {
+        let index_start = self.selection.start().unwrap_or(self.cursor).y as usize;
+        let index_end = self.selection.end().unwrap_or(self.cursor).y as usize;
+        if (dir < 0 && index_start == 0) || (dir > 0 && index_end + 1 >= self.buffer.line_count() as usize) { return; }
+        self.push_history(EditKind::Other);
+        if dir < 0 {
+            for i in index_start..=index_end { self.swap_lines(i, (i as i32 - 1).unsigned_abs() as usize); }
+        } else if dir > 0 {
+            for i in 0..=(index_end - index_start) { self.swap_lines(index_end - i, index_end - i + 1); }
+        }
+        if self.selection.is_valid() {
+            self.selection.set_start(Position::new(self.selection.start().unwrap().x, (self.selection.start().unwrap().y as i32 + dir) as u32));
+            self.selection.set_end(Position::new(self.selection.end().unwrap().x, (self.selection.end().unwrap().y as i32 + dir) as u32));
+        }
+        self.move_cursor(Position::new(self.cursor.x, (self.cursor.y as i32 + dir) as u32))
+    }
+
+    fn swap_lines(&mut self, a: usize, b: usize) {
+        let (a_index, b_index) = (a as u32, b as u32);
+        self.style_buffer = self.style_buffer.iter()
+            .flat_map(|style_range| style_range.split_lines(&[a_index, b_index], &self.buffer))
+            .map(|mut style_range| {
+                let (start, end) = (style_range.range.start.unwrap(), style_range.range.end.unwrap());
+                if start.y == end.y && (start.y == a_index || start.y == b_index) {
+                    let y = a_index + b_index - start.y; // The part moves with its line
+                    style_range.range = Range::new(Position::new(start.x, y), Position::new(end.x, y));
+                }
+                style_range
+            })
+            .collect();
+        let text_a = self.buffer.line_text(a_index);
+        let text_b = self.buffer.line_text(b_index);
+        let (first, last) = (cmp::min(a_index, b_index), cmp::max(a_index, b_index));
+        let edited_bytes = self.get_lines_bytes(first, last);
+        self.buffer.replace_line(a_index, &text_b);
+        self.buffer.replace_line(b_index, &text_a);
+        self.update_search_matches(edited_bytes, first, last);
+        for closer in &mut self.auto_closers { // the closers move with their line
+            if closer.y == a_index { closer.y = b_index; } else if closer.y == b_index { closer.y = a_index; }
+        }
+        self.alignments.swap(a, b);
+        self.changes.push(Change::SwapLines(a_index, b_index));
+    }
+
+    fn get_history_state(&self) -> HistoryState {
+        HistoryState {
+            buffer: self.buffer.clone(),
+            alignments: self.alignments.iter().copied().enumerate()
+                .filter(|(_, alignment)| *alignment != Alignment::Left)
+                .collect(),
+            style_buffer: self.style_buffer.clone(),
+            cursor: self.cursor,
+            selection: self.selection.get_range(),
+            secondary_cursors: self.secondary_cursors.iter().map(|c| (c.cursor, c.selection.get_range())).collect(),
+            revision: self.history.revision,
+        }
+    }
+
+    /// Save the current state as a new undo unit if the edit of `kind` can't be merged into the previous one
+    fn push_history(&mut self, kind: EditKind) {
+        if self.history.record_edit(kind, self.cursor) {
+            let state = self.get_history_state();
+            self.history.push(state);
+        }
+    }
+
+    fn restore_history_state(&mut self, state: HistoryState) {
+        self.buffer = state.buffer;
+        self.search_matches = Option::None;
+        self.auto_closers.clear();
+        self.style_buffer = state.style_buffer;
+        self.alignments = vec![Alignment::Left; self.buffer.line_count() as usize];
+        for (i, alignment) in state.alignments { self.alignments[i] = alignment; }
+        self.changes.push(Change::Reset(self.buffer.line_count()));
+        self.selection.reset();
+        if state.selection.is_valid() {
+            self.selection.set(state.selection.start.unwrap(), state.selection.end.unwrap());
+        }
+        self.cursor = state.cursor;
+        self.secondary_cursors = state.secondary_cursors.iter().map(|(position, range)| {
+            let mut secondary_cursor = SecondaryCursor::new(*position);
+            if range.is_valid() { secondary_cursor.selection.set(range.start.unwrap(), range.end.unwrap()); }
+            secondary_cursor
+        }).collect();
+        self.set_dirty(!self.history.is_saved());
+    }
+
+    pub fn undo(&mut self) {
+        let current_state = self.get_history_state();
+        if let Some(state) = self.history.undo(current_state) { self.restore_history_state(state); }
+    }
+
+    pub fn redo(&mut self) {
+        let current_state = self.get_history_state();
+        if let Some(state) = self.history.redo(current_state) { self.restore_history_state(state); }
+    }
+
+    /// Add a range to a buffer according to the underline/bold rules
+    fn add_range_to_buffer<T: RangeTrait>(range_like: T, buffer: &mut Vec<T>) {
+        // switch on the type of the generic parameter to determine wether it's a simple Range or a StyledRange
+        let range = range_like.get_range();
+        if !range.is_valid() { return; }
+        let len = buffer.len();
+        for mut i in 0 .. len {
+            assert!(len >= 1);
+            i = len - 1 - i;
+            let buffer_range = buffer.get_mut(i).unwrap().get_range();
+            if range == buffer_range { buffer.remove(i); return; }
+            else if range.include(buffer_range) { buffer.remove(i); }
+            else if buffer_range.include(range) {
+                assert!(buffer_range.is_valid());
+                let before = T::new(buffer_range.get_real_start().unwrap(), range.get_real_start().unwrap());
+                let after = T::new(range.get_real_end().unwrap(), buffer_range.get_real_end().unwrap());
+                if before.is_valid() { buffer.push(before);  }
+                if after.is_valid() { buffer.push(after);  }
+                buffer.remove(i);
+                return;
+            }
+        }
+        buffer.push(range_like); // to push the proper struct and not only the range
+    }
+
+    pub fn underline(&mut self) {
+        if !self.selection.is_valid() { return; }
+        self.push_history(EditKind::Other);
+        Self::add_range_to_buffer(StyleRange::new_underline(self.selection.get_range()), &mut self.style_buffer);
+        self.set_dirty(true);
+    }
+
+    pub fn bold(&mut self) {
+        if !self.selection.is_valid() { return; }
+        self.push_history(EditKind::Other);
+        self.invalidate_styles_at(self.selection.get_range());
+        Self::add_range_to_buffer(StyleRange::new_bold(self.selection.get_range()), &mut self.style_buffer);
+        self.set_dirty(true);
+    }
+
+    pub fn colorize(&mut self, color: TextColor) {
+        if !self.selection.is_valid() { return; }
+        self.push_history(EditKind::Other);
+        self.invalidate_styles_at(self.selection.get_range());
+        Self::add_range_to_buffer(StyleRange::new_colored(self.selection.get_range(), color), &mut self.style_buffer);
+        self.set_dirty(true);
+    }
+
+    /// Lay out again the lines of `range` and of the style ranges touching it, before their styles change
+    fn invalidate_styles_at(&mut self, range: Range) {
+        let (start, end) = (range.get_real_start().unwrap(), range.get_real_end().unwrap());
+        let touched_lines = self.style_buffer.iter()
+            .filter(|style_range| style_range.intersect(start, end).is_some())
+            .map(|style_range| Change::Restyle(style_range.get_real_start().unwrap().y, style_range.get_real_end().unwrap().y))
+            .chain([Change::Restyle(start.y, end.y)]);
+        self.changes.extend(touched_lines.collect::<Vec<_>>());
+    }
+
+    pub fn set_line_alignment(&mut self, alignment: Alignment) {
+        self.push_history(EditKind::Other);
+        let (start, end) = if self.selection.is_valid() {
+            (self.selection.start().unwrap().y, self.selection.end().unwrap().y)
+        } else { (self.cursor.y, self.cursor.y) };
+        for line_alignment in &mut self.alignments[start as usize ..= end as usize] { *line_alignment = alignment; }
+        self.changes.push(Change::Restyle(start, end));
+        self.set_dirty(true);
+    }
+
+    /// Limit the search to the current selection, if there is one, ex: when the find/replace panel is opened
+    pub fn set_search_scope(&mut self) {
+        self.search.scope = if self.selection.is_valid() && !self.selection.is_block() {
+            Some((self.selection.start().unwrap(), self.selection.end().unwrap()))
+        } else { Option::None };
+        self.search_matches = Option::None;
+    }
+
+    /// Filter the matches, in bytes of the text, that are in the search scope
+    fn filter_search_scope<T>(&self, matches: Vec<T>, bounds: fn(&T) -> (usize, usize)) -> Vec<T> {
+        let Some((start, end)) = self.search.get_scope() else { return matches; };
+        let (start, end) = (self.buffer.get_byte_index(start), self.buffer.get_byte_index(end));
+        matches.into_iter().filter(|found| start <= bounds(found).0 && bounds(found).1 <= end).collect()
+    }
+
+    /// The bytes from the start of the line `first` to the end of the line `last`
+    fn get_lines_bytes(&self, first: u32, last: u32) -> (usize, usize) {
+        (self.buffer.get_byte_index(Position::new(0, first)), self.buffer.get_byte_index(Position::new(self.buffer.line_len(last), last)))
+    }
+
+    /// Find the search matches again on the lines `first ..= last` only, that were between `edited_bytes` before the edit
+    /// The matches after them are shifted, they are all found again when a match can span several lines or the search has a scope
+    fn update_search_matches(&mut self, edited_bytes: (usize, usize), first: u32, last: u32) {
+        let Some(matches) = self.search_matches.take() else { return; };
+        if self.search.get_scope().is_some() || !self.search.is_line_bound() { return; }
+        let (start, end) = self.get_lines_bytes(first, last);
+        let text = self.buffer.get_text_between(Position::new(0, first), Position::new(self.buffer.line_len(last), last));
+        let before = matches.iter().take_while(|(_, found_end)| *found_end < edited_bytes.0).copied();
+        let edited = self.search.find_all(&text).into_iter().map(|(found_start, found_end)| (found_start + start, found_end + start));
+        let after = matches.iter().filter(|(found_start, _)| *found_start > edited_bytes.1).map(|(found_start, found_end)| (found_start + end - edited_bytes.1, found_end + end - edited_bytes.1));
+        self.search_matches = Some(before.chain(edited).chain(after).collect());
+    }
+
+    /// The matches of the search in its scope, in bytes of the text, found again after an edit of the search and of the edited lines after an edit of the buffer
+    pub fn get_search_matches(&mut self) -> &[(usize, usize)] {
+        if self.search_matches.is_none() {
+            let matches = self.search.find_all(&self.buffer.to_string());
+            self.search_matches = Some(self.filter_search_scope(matches, |found| *found));
+        }
+        self.search_matches.as_ref().unwrap()
+    }
+
+    /// The matches of the search in its scope with their replacement
+    fn get_search_replacements(&self) -> Vec<(Position, Position, String)> {
+        let matches = self.search.find_all_with_replacements(&self.buffer.to_string());
+        self.filter_search_scope(matches, |found| (found.start, found.end)).into_iter()
+            .map(|found| (self.buffer.get_position_at_byte(found.start), self.buffer.get_position_at_byte(found.end), found.replacement))
+            .collect()
+    }
+
+    /// Select the first match from `from`, or the last one before it, looping around the document
+    fn select_search_match(&mut self, from: Position, forward: bool) {
+        let from = self.buffer.get_byte_index(from);
+        let matches = self.get_search_matches();
+        let index = matches.partition_point(|(start, _)| *start < from);
+        let found = if forward { matches.get(index).or(matches.first()) } else { index.checked_sub(1).map(|i| &matches[i]).or(matches.last()) };
+        if let Some(&(start, end)) = found {
+            let (start, end) = (self.buffer.get_position_at_byte(start), self.buffer.get_position_at_byte(end));
+            self.remove_secondary_cursors();
+            self.selection.set(start, end);
+            self.move_cursor(end);
+        }
+    }
+
+    /// Search `query` while it is typed, from the start of the current match so it stays selected while it matches
+    pub fn search_for(&mut self, query: &str) {
+        self.search.query = query.to_string();
+        self.search_matches = Option::None;
+        let from = if self.selection.is_valid() { self.selection.start().unwrap() } else { self.cursor };
+        self.select_search_match(from, true);
+    }
+
+    pub fn find_next_match(&mut self) {
+        let from = if self.selection.is_valid() { self.selection.end().unwrap() } else { self.cursor };
+        self.select_search_match(from, true);
+    }
+
+    pub fn find_previous_match(&mut self) {
+        let from = if self.selection.is_valid() { self.selection.start().unwrap() } else { self.cursor };
+        self.select_search_match(from, false);
+    }
+
+    pub fn toggle_search_option(&mut self, option: SearchOption) {
+        self.search.toggle(option);
+        self.search_for(&self.search.query.clone());
+    }
+
+    /// Replace a match and keep the search scope around the same text, return the end of the replacement
+    fn replace_search_match(&mut self, start: Position, end: Position, replacement: &str) -> Position {
+        self.remove_text(start, end);
+        let replacement_end = self.insert_text(start, replacement);
+        if let Some((_, scope_end)) = &mut self.search.scope {
+            *scope_end = shift_after_insert(shift_after_remove(*scope_end, start, end), start, replacement_end);
+        }
+        replacement_end
+    }
+
+    /// Replace the selected match, if it is one, and select the next match
+    pub fn replace_match(&mut self) {
+        let selection = (self.selection.start(), self.selection.end());
+        let current_match = self.get_search_replacements().into_iter().find(|(start, end, _)| (Some(*start), Some(*end)) == selection);
+        if let Some((start, end, replacement)) = current_match {
+            self.push_history(EditKind::Other);
+            self.selection.reset();
+            let replacement_end = self.replace_search_match(start, end, &replacement);
+            self.move_cursor(replacement_end);
+            self.set_dirty(true);
+        }
+        self.find_next_match();
+    }
+
+    /// Replace all the matches as a single undo step, the style ranges follow the replaced text
+    pub fn replace_all_matches(&mut self) {
+        let matches = self.get_search_replacements();
+        if matches.is_empty() { return; }
+        self.push_history(EditKind::Other);
+        self.remove_secondary_cursors();
+        self.selection.reset();
+        // From the end so the positions of the remaining matches stay valid
+        for (start, end, replacement) in matches.iter().rev() { self.replace_search_match(*start, *end, replacement); }
+        self.move_cursor(self.cursor); // the line of the cursor may be shorter
+        self.set_dirty(true);
+    }
+
+    /// Whether `position` is inside the selection or on one of its bounds
+    pub fn is_on_selection(&self, position: Position) -> bool {
+        let (Some(start), Some(end)) = (self.selection.start(), self.selection.end()) else { return false; };
+        !is_before(position, start) && !is_before(end, position)
+    }
+
+    /// Move the selected text to `position`, or copy it there, its styles go with it and it stays selected
+    pub fn move_selected_text(&mut self, position: Position, is_copy: bool) {
+        let (start, end) = (self.selection.start().unwrap(), self.selection.end().unwrap());
+        let text = self.buffer.get_text_between(start, end);
+        let styles: Vec<StyleRange> = self.style_buffer.iter().filter_map(|style_range| style_range.intersect(start, end)).collect();
+        self.push_history(EditKind::Other);
+        let target = if is_copy { position } else {
+            self.remove_text(start, end);
+            shift_after_remove(position, start, end)
+        };
+        let target_end = self.insert_text(target, &text);
+        self.style_buffer.extend(styles.iter().filter_map(|style_range| style_range.translate(start, target)));
+        self.selection.set(target, target_end);
+        self.move_cursor(target_end);
+        self.set_dirty(true);
+    }
+
+    /// Move the cursor in front of the bracket or quote matching the one next to it
+    pub fn jump_to_matching_bracket(&mut self) {
+        let Some((_, other)) = find_bracket_pair(&self.buffer, self.cursor) else { return; };
+        self.remove_secondary_cursors();
+        self.selection.reset();
+        self.move_cursor(other);
+    }
+
+    /// Select the text inside the innermost brackets or quotes around the cursor, then with them when it is already selected
+    pub fn select_inside_brackets(&mut self) {
+        let from = if self.selection.is_valid() { self.selection.start().unwrap() } else { self.cursor };
+        let Some((open, close)) = find_enclosing_pair(&self.buffer, from) else { return; };
+        let inside = (Position::new(open.x + 1, open.y), close);
+        let (start, end) = if (self.selection.start(), self.selection.end()) == (Some(inside.0), Some(inside.1)) {
+            (open, Position::new(close.x + 1, close.y))
+        } else { inside };
+        self.remove_secondary_cursors();
+        self.selection.set(start, end);
+        self.move_cursor(end);
+    }
+
+    /// Move the cursor to `target`, ex: `120`, `120:5` or `-3`
+    pub fn go_to_target(&mut self, target: GoTo) {
+        let position = target.get_position(self.cursor);
+        self.remove_secondary_cursors();
+        self.selection.reset();
+        self.move_cursor(position);
+    }
+
+    /// Replace the whole document
+    pub fn set_text(&mut self, text: &str) {
+        self.buffer = TextBuffer::from(text);
+        self.search_matches = Option::None;
+        self.auto_closers.clear();
+        self.alignments = vec![Alignment::Left; self.buffer.line_count() as usize];
+        self.changes.push(Change::Reset(self.buffer.line_count()));
+        self.remove_secondary_cursors();
+    }
+
+    /// Replace the whole document by the text of a file, without styles nor history
+    pub fn load_text(&mut self, text: &str) {
+        self.style_buffer = vec![];
+        self.history.clear();
+        self.is_dirty = false;
+        self.selection.reset();
+        self.set_text(text);
+        self.cursor = Position::ZERO;
+    }
+}
+
+/// Split pairs written as two graphemes, ex: `()`, the other ones are ignored
+pub fn parse_auto_pairs(pairs: &[&str]) -> Vec<(String, String)> {
+    pairs.iter().filter_map(|pair| {
+        let graphemes: Vec<&str> = pair.graphemes(true).collect();
+        if graphemes.len() == 2 { Some((graphemes[0].to_string(), graphemes[1].to_string())) } else { Option::None }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::{parse_auto_pairs, Alignment, Change, Document, Modifiers, PlainLayout};
+    use crate::document::go_to::GoTo;
+    use crate::document::indentation::Indentation;
+    use crate::document::position::Position;
+    use crate::document::range::Range;
+    use crate::document::range_trait::RangeTrait;
+    use crate::document::search::SearchOption;
+    use crate::document::style_range::StyleRange;
+
+    /// A document alone, without fonts nor window, as a script would edit it
+    fn document_with_text(text: &str) -> Document {
+        let mut document = Document::new();
+        document.set_text(text);
+        document
+    }
+
+    fn type_text(document: &mut Document, text: &str) {
+        for c in text.chars() { document.add_char(&c.to_string()); }
+    }
+
+    #[test]
+    fn type_and_delete() {
+        let mut document = document_with_text("");
+        type_text(&mut document, "hello");
+        assert_eq!(document.buffer.to_string(), "hello");
+        assert_eq!(document.cursor, Position::new(5, 0));
+        document.delete_char();
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), "hel");
+        assert_eq!(document.cursor, Position::new(3, 0));
+    }
+
+    #[test]
+    fn matching_template() {
+        let mut document = document_with_text("");
+        type_text(&mut document, "(");
+        assert_eq!(document.buffer.to_string(), "()");
+        assert_eq!(document.cursor, Position::new(1, 0));
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), "");
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut document = document_with_text("");
+        type_text(&mut document, "hello world");
+        document.undo();
+        assert_eq!(document.buffer.to_string(), "hello ");
+        document.undo();
+        assert_eq!(document.buffer.to_string(), "");
+        document.redo();
+        assert_eq!(document.buffer.to_string(), "hello ");
+        assert_eq!(document.cursor, Position::new(6, 0));
+    }
+
+    #[test]
+    fn move_and_delete_by_grapheme() {
+        let mut document = document_with_text("e\u{301}a");
+        assert_eq!(document.buffer.line_len(0), 2);
+        document.move_cursor_relative(1, 0, &PlainLayout);
+        assert_eq!(document.cursor, Position::new(1, 0));
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), "a");
+    }
+
+    #[test]
+    fn move_by_words_and_lines() {
+        let mut document = document_with_text("one two\nthree");
+        document.modifiers = Modifiers { alt: true, ..Modifiers::default() };
+        document.move_cursor_relative(1, 0, &PlainLayout);
+        assert_eq!(document.cursor, Position::new(3, 0));
+        document.modifiers = Modifiers { shift: true, ..Modifiers::default() };
+        document.move_cursor_relative(0, 1, &PlainLayout);
+        assert_eq!(document.get_selected_text(), " two\nthr");
+        document.modifiers = Modifiers::default();
+        document.move_cursor_relative(0, -1, &PlainLayout); // to the start of the selection
+        assert_eq!(document.cursor, Position::new(3, 0));
+        assert!(!document.selection.is_valid());
+    }
+
+    #[test]
+    fn type_with_multiple_cursors() {
+        let mut document = document_with_text("a\nb\nc");
+        document.move_cursor(Position::new(1, 0));
+        document.add_cursor(Position::new(1, 1));
+        document.add_cursor(Position::new(1, 2));
+        type_text(&mut document, "xy");
+        assert_eq!(document.buffer.to_string(), "axy\nbxy\ncxy");
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), "ax\nbx\ncx");
+        document.new_line();
+        assert_eq!(document.buffer.to_string(), "ax\n\nbx\n\ncx\n");
+        assert_eq!(document.secondary_cursors.len(), 2);
+    }
+
+    #[test]
+    fn cut_with_multiple_cursors() {
+        let mut document = document_with_text("one two\nthree four");
+        document.selection.set(Position::new(0, 0), Position::new(3, 0));
+        document.move_cursor(Position::new(3, 0));
+        document.add_cursor(Position::new(5, 1));
+        document.secondary_cursors[0].selection.set(Position::new(0, 1), Position::new(5, 1));
+        assert_eq!(document.cut(), Some("one\nthree".to_string()));
+        assert_eq!(document.buffer.to_string(), " two\n four");
+        document.undo();
+        assert_eq!(document.buffer.to_string(), "one two\nthree four"); // a single undo unit
+        document.paste("1\n3");
+        assert_eq!(document.buffer.to_string(), "1 two\n3 four"); // a line for each cursor
+    }
+
+    #[test]
+    fn overlapping_cursors_merge() {
+        let mut document = document_with_text("ab");
+        document.move_cursor(Position::new(1, 0));
+        document.add_cursor(Position::new(2, 0));
+        assert_eq!(document.secondary_cursors.len(), 1);
+        document.move_cursor_relative(-1, 0, &PlainLayout);
+        document.move_cursor_relative(-1, 0, &PlainLayout);
+        assert!(document.secondary_cursors.is_empty());
+        assert_eq!(document.cursor, Position::new(0, 0));
+    }
+
+    #[test]
+    fn select_next_occurrence() {
+        let mut document = document_with_text("foo bar foo");
+        document.selection.set(Position::new(0, 0), Position::new(3, 0));
+        document.select_next_occurrence();
+        assert_eq!(document.secondary_cursors.len(), 1);
+        type_text(&mut document, "x");
+        assert_eq!(document.buffer.to_string(), "x bar x");
+    }
+
+    #[test]
+    fn block_selection() {
+        let mut document = document_with_text("abc\nd\nghi");
+        document.selection.set(Position::new(1, 0), Position::new(2, 2));
+        document.selection.set_block(true);
+        assert_eq!(document.get_selected_text(), "b\n\nh");
+        document.delete_selection();
+        assert_eq!(document.buffer.to_string(), "ac\nd\ngi");
+        type_text(&mut document, "-");
+        assert_eq!(document.buffer.to_string(), "a-c\nd-\ng-i");
+        document.undo();
+        document.undo(); // the block was removed as a single undo unit
+        assert_eq!(document.buffer.to_string(), "abc\nd\nghi");
+    }
+
+    #[test]
+    fn styles_follow_edits() {
+        let mut document = document_with_text("hello world");
+        document.style_buffer.push(StyleRange::new_bold(Range::new(Position::new(6, 0), Position::new(11, 0))));
+        type_text(&mut document, "A "); // before the style
+        assert_eq!(document.style_buffer[0].range, Range::new(Position::new(8, 0), Position::new(13, 0)));
+        document.move_cursor(Position::new(10, 0));
+        type_text(&mut document, "x"); // inside the style
+        assert_eq!(document.style_buffer[0].range, Range::new(Position::new(8, 0), Position::new(14, 0)));
+        document.selection.set(Position::new(4, 0), Position::new(9, 0));
+        document.delete_selection(); // across the start of the style
+        assert_eq!(document.style_buffer[0].range, Range::new(Position::new(4, 0), Position::new(9, 0)));
+        document.selection.set(Position::new(3, 0), Position::new(9, 0));
+        document.delete_selection();
+        assert!(document.style_buffer.is_empty());
+    }
+
+    #[test]
+    fn styles_follow_moved_lines() {
+        let mut document = document_with_text("abc\ndef");
+        document.style_buffer.push(StyleRange::new_underline(Range::new(Position::new(1, 0), Position::new(2, 1))));
+        document.switch_lines(1);
+        assert_eq!(document.buffer.to_string(), "def\nabc");
+        let mut ranges: Vec<Range> = document.style_buffer.iter().map(|style_range| style_range.range).collect();
+        ranges.sort_by_key(|range| range.start.unwrap().y);
+        assert_eq!(ranges, vec![Range::new(Position::new(0, 0), Position::new(2, 0)), Range::new(Position::new(1, 1), Position::new(3, 1))]);
+        document.duplicate_line();
+        assert_eq!(document.buffer.to_string(), "def\nabc\nabc");
+        assert_eq!(document.style_buffer.len(), 3);
+    }
+
+    #[test]
+    fn duplicated_line_keeps_its_styles() {
+        let mut document = document_with_text("abc\ndef");
+        document.style_buffer.push(StyleRange::new_bold(Range::new(Position::new(1, 0), Position::new(2, 1)))); // starts on the line above
+        document.move_cursor(Position::new(0, 1));
+        document.duplicate_line();
+        assert_eq!(document.buffer.to_string(), "abc\ndef\ndef");
+        let mut ranges: Vec<Range> = document.style_buffer.iter().map(|style_range| style_range.range).collect();
+        ranges.sort_by_key(|range| range.start.unwrap().y);
+        assert_eq!(ranges, vec![
+            Range::new(Position::new(1, 0), Position::new(3, 0)),
+            Range::new(Position::new(0, 1), Position::new(2, 1)),
+            Range::new(Position::new(0, 2), Position::new(2, 2)),
+        ]);
+    }
+
+    #[test]
+    fn alignments_follow_the_lines() {
+        let mut document = document_with_text("a\nb\nc");
+        document.move_cursor(Position::new(1, 1));
+        document.set_line_alignment(Alignment::Center);
+        document.new_line();
+        assert_eq!(document.alignments, vec![Alignment::Left, Alignment::Center, Alignment::Center, Alignment::Left]);
+        document.switch_lines(-1);
+        assert_eq!(document.alignments, vec![Alignment::Left, Alignment::Center, Alignment::Center, Alignment::Left]);
+        document.selection.set(Position::new(0, 0), Position::new(0, 2));
+        document.delete_selection();
+        assert_eq!(document.alignments, vec![Alignment::Left, Alignment::Left]); // the first line keeps its alignment
+        document.undo();
+        assert_eq!(document.alignments.len(), 4);
+        assert_eq!(document.alignments[1], Alignment::Center);
+    }
+
+    #[test]
+    fn changes_describe_the_edits() {
+        let mut document = document_with_text("ab\ncd");
+        document.take_changes();
+        document.move_cursor(Position::new(1, 0));
+        document.new_line();
+        assert_eq!(document.take_changes(), vec![Change::Insert(Position::new(1, 0), Position::new(0, 1))]);
+        document.delete_char();
+        assert_eq!(document.take_changes(), vec![Change::Remove(Position::new(1, 0), Position::new(0, 1))]);
+        document.undo();
+        assert_eq!(document.take_changes(), vec![Change::Reset(3)]);
+        assert!(document.take_changes().is_empty());
+    }
+
+    #[test]
+    fn find_and_replace() {
+        let mut document = document_with_text("one two\nOne three one");
+        document.search_for("one");
+        assert_eq!(document.get_selected_text(), "one");
+        document.find_next_match();
+        assert_eq!((document.selection.start(), document.selection.end()), (Some(Position::new(0, 1)), Some(Position::new(3, 1))));
+        document.find_previous_match();
+        document.find_previous_match(); // loops around the document
+        assert_eq!(document.selection.start(), Some(Position::new(10, 1)));
+        document.search.replacement = "1".to_string();
+        document.replace_match();
+        assert_eq!(document.buffer.to_string(), "one two\nOne three 1");
+        assert_eq!(document.selection.start(), Some(Position::new(0, 0))); // the next match is selected
+        document.search.scope = Some((Position::new(0, 1), Position::new(5, 1)));
+        document.toggle_search_option(SearchOption::InSelection);
+        document.replace_all_matches();
+        assert_eq!(document.buffer.to_string(), "one two\n1 three 1");
+        document.undo();
+        assert_eq!(document.buffer.to_string(), "one two\nOne three 1");
+    }
+
+    #[test]
+    fn search_matches_follow_moved_lines() {
+        let mut document = document_with_text("a\nbb a");
+        document.search_for("a");
+        assert_eq!(document.get_search_matches(), &[(0, 1), (5, 6)]);
+        document.switch_lines(1);
+        assert_eq!(document.get_search_matches(), &[(3, 4), (5, 6)]);
+    }
+
+    #[test]
+    fn search_matches_of_the_edited_lines() {
+        let mut document = document_with_text("ab\ncd ab\nab");
+        document.search_for("ab");
+        document.selection.reset();
+        assert_eq!(document.get_search_matches(), &[(0, 2), (6, 8), (9, 11)]);
+        document.move_cursor(Position::new(2, 1));
+        type_text(&mut document, "a\nb");
+        assert_eq!(document.buffer.to_string(), "ab\ncda\nb ab\nab");
+        assert_eq!(document.get_search_matches(), &[(0, 2), (9, 11), (12, 14)]);
+        document.remove_text(Position::new(3, 1), Position::new(0, 2));
+        assert_eq!(document.get_search_matches(), &[(0, 2), (5, 7), (8, 10), (11, 13)]);
+        let found = document.search.find_all(&document.buffer.to_string());
+        assert_eq!(document.get_search_matches(), found.as_slice()); // the same as a full search
+    }
+
+    #[test]
+    fn replace_all_keeps_styles() {
+        let mut document = document_with_text("a bb a bb");
+        document.style_buffer.push(StyleRange::new_bold(Range::new(Position::new(2, 0), Position::new(9, 0))));
+        document.search.query = "a".to_string();
+        document.search.replacement = "ccc".to_string();
+        document.replace_all_matches();
+        assert_eq!(document.buffer.to_string(), "ccc bb ccc bb");
+        let range = document.style_buffer[0].range;
+        assert_eq!((range.start, range.end), (Some(Position::new(4, 0)), Some(Position::new(13, 0))));
+    }
+
+    #[test]
+    fn auto_pairs() {
+        let mut document = document_with_text("");
+        type_text(&mut document, "f(a)");
+        assert_eq!(document.buffer.to_string(), "f(a)"); // the typed `)` steps over the inserted one
+        type_text(&mut document, " l'eau \"");
+        assert_eq!(document.buffer.to_string(), "f(a) l'eau \"\"");
+        document.selection.set(Position::new(2, 0), Position::new(3, 0));
+        type_text(&mut document, "[");
+        assert_eq!(document.buffer.to_string(), "f([a]) l'eau \"\"");
+        assert_eq!(document.get_selected_text(), "a"); // the wrapped text stays selected
+        document.auto_pairs = parse_auto_pairs(&["«»"]);
+        document.set_text("");
+        type_text(&mut document, "(«");
+        assert_eq!(document.buffer.to_string(), "(«»");
+        document.auto_pairs = parse_auto_pairs(&["()"]);
+        document.set_text("\nx)");
+        document.move_cursor(Position::ZERO);
+        type_text(&mut document, "(");
+        document.switch_lines(1);
+        type_text(&mut document, ")");
+        assert_eq!(document.buffer.to_string(), "x)\n()"); // the closer moved with its line
+        document.move_cursor(Position::new(1, 0));
+        type_text(&mut document, ")");
+        assert_eq!(document.buffer.to_string(), "x))\n()");
+    }
+
+    #[test]
+    fn delete_auto_pairs() {
+        let mut document = document_with_text("");
+        document.auto_pairs = parse_auto_pairs(&["«»", "()"]);
+        type_text(&mut document, "«");
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), ""); // the inserted closer goes with its opener
+        type_text(&mut document, "()");
+        document.move_cursor(Position::new(1, 0));
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), ")"); // the typed closer stays
+        document.set_text("[]");
+        document.move_cursor(Position::new(1, 0));
+        document.delete_char();
+        assert_eq!(document.buffer.to_string(), "]"); // not a configured pair
+    }
+
+    #[test]
+    fn go_to_line() {
+        let mut document = document_with_text("one\ntwo\nthree");
+        document.go_to_target(GoTo::parse("3:4").unwrap());
+        assert_eq!(document.cursor, Position::new(3, 2));
+        document.go_to_target(GoTo::parse("-1").unwrap());
+        assert_eq!(document.cursor, Position::new(3, 1));
+        document.go_to_target(GoTo::parse("99:99").unwrap()); // clamped to the document
+        assert_eq!(document.cursor, Position::new(5, 2));
+    }
+
+    #[test]
+    fn continue_lists() {
+        let mut document = document_with_text("1. one\n2. two");
+        document.move_cursor(Position::new(6, 0));
+        document.new_line();
+        type_text(&mut document, "half");
+        assert_eq!(document.buffer.to_string(), "1. one\n2. half\n3. two"); // the next items are numbered again
+        document.indent_at_cursors(false);
+        assert_eq!(document.buffer.to_string(), "1. one\n    1. half\n2. two");
+        assert_eq!(document.cursor, Position::new(11, 1));
+        document.indent_at_cursors(true);
+        assert_eq!(document.buffer.to_string(), "1. one\n2. half\n3. two");
+        document.set_text("- [x] task");
+        document.move_cursor(Position::new(10, 0));
+        document.new_line();
+        assert_eq!(document.buffer.to_string(), "- [x] task\n- [ ] ");
+        document.new_line(); // an empty item ends the list
+        assert_eq!(document.buffer.to_string(), "- [x] task\n");
+    }
+
+    #[test]
+    fn undo_a_list_continuation() {
+        let mut document = document_with_text("1. one\n2. two");
+        document.move_cursor(Position::new(6, 0));
+        type_text(&mut document, "s");
+        document.new_line();
+        assert_eq!(document.buffer.to_string(), "1. ones\n2. \n3. two");
+        document.undo(); // the new item and the numbering of the next ones are a single step
+        assert_eq!(document.buffer.to_string(), "1. ones\n2. two");
+        assert_eq!(document.cursor, Position::new(7, 0));
+    }
+
+    #[test]
+    fn indent_lines() {
+        let mut document = document_with_text("if a {\n    b\n\n}");
+        document.move_cursor(Position::new(5, 1));
+        document.new_line();
+        assert_eq!(document.buffer.to_string(), "if a {\n    b\n    \n\n}"); // the indentation is kept
+        document.selection.set(Position::new(0, 0), Position::new(0, 3));
+        document.indent_at_cursors(false);
+        assert_eq!(document.buffer.to_string(), "    if a {\n        b\n        \n\n}");
+        assert_eq!(document.get_selected_text(), "    if a {\n        b\n        \n");
+        document.indent_lines_at_cursors(true);
+        document.indent_lines_at_cursors(true);
+        assert_eq!(document.buffer.to_string(), "if a {\nb\n\n\n}");
+        document.indentation = Indentation { use_tabs: true, width: 4 };
+        document.set_text("ab");
+        document.selection.reset();
+        document.move_cursor(Position::new(2, 0));
+        document.indent_at_cursors(false);
+        assert_eq!(document.buffer.to_string(), "ab\t");
+    }
+
+    #[test]
+    fn drag_selected_text() {
+        let mut document = document_with_text("one two\nthree");
+        document.style_buffer.push(StyleRange::new_bold(Range::new(Position::new(1, 0), Position::new(3, 0))));
+        document.selection.set(Position::new(0, 0), Position::new(4, 0));
+        assert!(document.is_on_selection(Position::new(4, 0)));
+        document.move_selected_text(Position::new(5, 1), false);
+        assert_eq!(document.buffer.to_string(), "two\nthreeone ");
+        assert_eq!(document.get_selected_text(), "one ");
+        assert_eq!(document.style_buffer[0].range, Range::new(Position::new(6, 1), Position::new(8, 1)));
+        document.move_selected_text(Position::new(0, 0), true);
+        assert_eq!(document.buffer.to_string(), "one two\nthreeone ");
+        assert_eq!(document.style_buffer.len(), 2);
+    }
+}
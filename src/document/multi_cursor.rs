@@ -0,0 +1,54 @@
+use crate::document::position::Position;
+use crate::document::range::{is_before, vector_max, vector_min};
+use crate::document::range_trait::RangeTrait;
+use crate::document::selection::Selection;
+
+/// A cursor added next to the main one (alt+click, select next occurrence, add cursor above/below), with its own selection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SecondaryCursor {
+    pub cursor: Position,
+    pub selection: Selection,
+}
+
+impl SecondaryCursor {
+    pub fn new(position: Position) -> Self {
+        Self { cursor: position, selection: Selection::default() }
+    }
+
+    pub fn bounds(&self) -> (Position, Position) { get_bounds(self.cursor, &self.selection) }
+
+    /// Move the cursor and its selection after an edit made by another cursor
+    pub fn shift(&mut self, shift_position: impl Fn(Position) -> Position) {
+        self.cursor = shift_position(self.cursor);
+        let range = self.selection.get_range();
+        if !range.is_valid() { return; }
+        let (start, end) = (shift_position(range.start.unwrap()), shift_position(range.end.unwrap()));
+        if start == end { self.selection.reset(); } else { self.selection.set(start, end); }
+    }
+}
+
+/// The part of the text covered by a cursor: its selection, or an empty range at its position
+pub fn get_bounds(cursor: Position, selection: &Selection) -> (Position, Position) {
+    if selection.is_valid() { return (selection.start().unwrap(), selection.end().unwrap()); }
+    (cursor, cursor)
+}
+
+/// Two cursors overlap if they share some text or a position, two selections that only touch each other don't
+pub fn overlap(a: (Position, Position), b: (Position, Position)) -> bool {
+    if is_before(a.1, b.0) || is_before(b.1, a.0) { return false; }
+    let are_selections = a.0 != a.1 && b.0 != b.1;
+    !(are_selections && (a.1 == b.0 || b.1 == a.0))
+}
+
+/// Extend `cursor` and its selection so they also cover `other`
+pub fn merge(cursor: &mut Position, selection: &mut Selection, other: &SecondaryCursor) {
+    let (start, end) = get_bounds(*cursor, selection);
+    let (other_start, other_end) = other.bounds();
+    let (new_start, new_end) = (vector_min(start, other_start), vector_max(end, other_end));
+    if new_start == new_end { return; } // Both cursors are at the same place
+    // The cursor stays on the same side of the selection
+    let is_at_start = if selection.is_valid() { *cursor == start } else { other.cursor == other_start && other.selection.is_valid() };
+    let (anchor, head) = if is_at_start { (new_end, new_start) } else { (new_start, new_end) };
+    selection.set(anchor, head);
+    *cursor = head;
+}
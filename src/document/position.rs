@@ -0,0 +1,16 @@
+/// A place in the text as (column, line), where a column is a grapheme cluster
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Position {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Position {
+    pub const ZERO: Self = Self { x: 0, y: 0 };
+
+    pub const fn new(x: u32, y: u32) -> Self { Self { x, y } }
+}
+
+impl From<(u32, u32)> for Position {
+    fn from((x, y): (u32, u32)) -> Self { Self::new(x, y) }
+}
@@ -1,26 +1,18 @@
-use std::cell::RefCell;
+use std::cmp;
 use std::fmt::{Debug, Formatter};
-use std::rc::Rc;
 
-use speedy2d::color::Color;
-use speedy2d::dimen::Vector2;
-use speedy2d::Graphics2D;
-use speedy2d::shape::Rectangle;
+use crate::document::position::Position;
+use crate::document::range_trait::RangeTrait;
+use crate::document::text_buffer::TextBuffer;
 
-use crate::camera::Camera;
-use crate::font::Font;
-use crate::line::Line;
-use crate::range_trait::RangeTrait;
-
-pub fn get_line_length(i: u32, lines: &[Line]) -> u32 {
-    if i + 1 > lines.len() as u32 { return 0; } // Prevent overflow
-    lines[i as usize].buffer.len() as u32
+pub fn get_line_length(i: u32, buffer: &TextBuffer) -> u32 {
+    buffer.line_len(i)
 }
 
 #[derive(Clone, Copy)]
 pub struct Range {
-    pub start: Option<Vector2<u32>>,
-    pub end: Option<Vector2<u32>>,
+    pub start: Option<Position>,
+    pub end: Option<Position>,
 }
 
 impl Default for Range {
@@ -46,19 +38,56 @@ impl PartialEq for Range {
     }
 }
 
-pub fn vector_max(v1: Vector2<u32>, v2: Vector2<u32>) -> Vector2<u32> {
+pub fn vector_max(v1: Position, v2: Position) -> Position {
     if v1.y < v2.y { return v2 }
     if v1.y > v2.y { return v1  }
     if v1.x < v2.x { return v2 }
     else { return v1 }
 }
 
-pub fn vector_min(v1: Vector2<u32>, v2: Vector2<u32>) -> Vector2<u32> {
+pub fn vector_min(v1: Position, v2: Position) -> Position {
     if vector_max(v1, v2) == v2 { v1 } else { v2 }
 }
 
+pub fn is_before(a: Position, b: Position) -> bool { a != b && vector_min(a, b) == a }
+
+/// Position of `position` once the text between `start` and `end` has been inserted
+pub fn shift_after_insert(position: Position, start: Position, end: Position) -> Position {
+    if is_before(position, start) { return position; }
+    if position.y == start.y { Position::new(end.x + position.x - start.x, end.y) }
+    else { Position::new(position.x, position.y + end.y - start.y) }
+}
+
+/// Position of `position` once the text between `start` and `end` has been removed
+pub fn shift_after_remove(position: Position, start: Position, end: Position) -> Position {
+    if !is_before(start, position) { return position; }
+    if !is_before(end, position) { return start; } // The position was inside the removed text
+    if position.y == end.y { Position::new(start.x + position.x - end.x, start.y) }
+    else { Position::new(position.x, position.y - (end.y - start.y)) }
+}
+
+/// Position of `position`, found after `from`, once its text is moved to `to`
+pub fn translate(position: Position, from: Position, to: Position) -> Position {
+    shift_after_insert(shift_after_remove(position, Position::ZERO, from), Position::ZERO, to)
+}
+
+impl Range {
+    /// The columns covered on each line when the range is a block going from the `start` corner to the `end` one
+    pub fn get_block_lines_index(&self, buffer: &TextBuffer) -> Vec<(u32, u32)> {
+        if !self.is_valid() { return vec![]; }
+        let (start, end) = (self.start.unwrap(), self.end.unwrap());
+        let (left, right) = (cmp::min(start.x, end.x), cmp::max(start.x, end.x));
+        (cmp::min(start.y, end.y) ..= cmp::max(start.y, end.y))
+            .map(|y| {
+                let line_length = get_line_length(y, buffer);
+                (cmp::min(left, line_length), cmp::min(right, line_length))
+            })
+            .collect()
+    }
+}
+
 impl RangeTrait for Range {
-    fn new(start: Vector2<u32>, end: Vector2<u32>) -> Self {
+    fn new(start: Position, end: Position) -> Self {
         Self {
             start: Some(start),
             end: Some(end),
@@ -67,12 +96,12 @@ impl RangeTrait for Range {
 
     fn get_range(&self) -> &Range { &self }
 
-    fn start(&mut self, position: Vector2<u32>) {
-        self.start = Some(Vector2::new(position.x, position.y));
+    fn start(&mut self, position: Position) {
+        self.start = Some(Position::new(position.x, position.y));
     }
 
-    fn end(&mut self, position: Vector2<u32>) {
-        self.end = Some(Vector2::new(position.x, position.y));
+    fn end(&mut self, position: Position) {
+        self.end = Some(Position::new(position.x, position.y));
     }
 
     fn reset(&mut self) {
@@ -110,12 +139,12 @@ impl RangeTrait for Range {
         format!("{}-{}-{}-{}", start.x, start.y, end.x, end.y)
     }
 
-    fn get_real_start(&self) -> Option<Vector2<u32>> {
+    fn get_real_start(&self) -> Option<Position> {
         if !self.is_valid() { return Option::None; }
         Some(vector_min(self.start.unwrap(), self.end.unwrap()))
     }
 
-    fn get_real_end(&self) -> Option<Vector2<u32>> {
+    fn get_real_end(&self) -> Option<Position> {
         if !self.is_valid() { return Option::None; }
         Some(vector_max(self.start.unwrap(), self.end.unwrap()))
     }
@@ -136,12 +165,12 @@ impl RangeTrait for Range {
                 .map(|n| n.parse::<u32>());
             if parsed_number.clone().any(|n| n.is_err()) { continue }
             let coords: Vec<u32> = parsed_number.map(|n| n.unwrap()).collect();
-            result.push(Range::new(Vector2::new(coords[0], coords[1]),  Vector2::new(coords[2], coords[3])))
+            result.push(Range::new(Position::new(coords[0], coords[1]),  Position::new(coords[2], coords[3])))
         }
         result
     }
 
-    fn get_lines_index(&mut self, lines: &[Line]) -> Vec<(u32, u32)> {
+    fn get_lines_index(&mut self, buffer: &TextBuffer) -> Vec<(u32, u32)> {
         // relative index of selection starting in the self.start.y index
         if !self.is_valid() { return vec![]; }
         let start = self.get_real_start().unwrap();
@@ -150,30 +179,9 @@ impl RangeTrait for Range {
         for y in start.y..=end.y {
             if y == start.y {
                 if start.y == end.y {
-                    result.push((start.x, end.x)) } else { result.push((start.x, get_line_length(y, lines)))
+                    result.push((start.x, end.x)) } else { result.push((start.x, get_line_length(y, buffer)))
                 }
-            } else if y == end.y { result.push((0, end.x)) } else { result.push((0, get_line_length(y, lines))) }
+            } else if y == end.y { result.push((0, end.x)) } else { result.push((0, get_line_length(y, buffer))) }
         }
         result
-    }
-
-    fn _render(&mut self, font: Rc<RefCell<Font>>, lines: &[Line], camera: &Camera, graphics: &mut Graphics2D) {
-        if !self.is_valid() { return; }
-        let font_width = font.borrow().char_width;
-        let font_height = font.borrow().char_height;
-        let initial_y = self.get_real_start().unwrap().y as f32 * font_height - camera.computed_y();
-        for (i, indices) in self.get_lines_index(lines).iter().enumerate() { // TODO: cache ?
-            let line_y = initial_y + i as f32 * font_height;
-            let line = &lines[self.get_real_start().unwrap().y as usize + i];
-            let line_offset = line.alignment_offset;
-            let line_camera = Camera::from_with_offset(camera, Vector2::new(-line_offset, 0.));
-            graphics.draw_rectangle(
-                Rectangle::new(
-                    Vector2::new(indices.0 as f32 * font_width - line_camera.computed_x(), line_y),
-                    Vector2::new(indices.1 as f32 * font_width - line_camera.computed_x(), line_y + font_height),
-                ),
-                Color::from_int_rgba(100, 100, 100, 50),
-            )
-        }
-    }
-}
\ No newline at end of file
+    }}
\ No newline at end of file
@@ -0,0 +1,31 @@
+use crate::document::position::Position;
+use crate::document::range::Range;
+use crate::document::text_buffer::TextBuffer;
+
+pub trait RangeTrait {
+    fn new(start: Position, end: Position) -> Self;
+
+    fn get_range(&self) -> &Range;
+
+    fn start(&mut self, position: Position);
+
+    fn end(&mut self, position: Position);
+
+    fn reset(&mut self);
+
+    fn add(&mut self, other: Self);
+
+    fn include(&self, other: &Self) -> bool;
+
+    fn is_valid(&self) -> bool;
+
+    fn get_id(&self) -> String;
+
+    fn get_real_start(&self) -> Option<Position>;
+
+    fn get_real_end(&self) -> Option<Position>;
+
+    fn get_ranges_from_drn_line(pattern: &str, lines: &Vec<&str>) -> Vec<Range>;
+
+    fn get_lines_index(&mut self, buffer: &TextBuffer) -> Vec<(u32, u32)>;
+}
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::cell::RefCell;
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::{Anchor, Class, Hir, HirKind, Literal};
+use regex_syntax::ParserBuilder;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+use crate::document::position::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchOption {
+    Regex,
+    MatchCase,
+    WholeWord,
+    InSelection,
+}
+
+impl SearchOption {
+    pub const ALL: [SearchOption; 4] = [SearchOption::Regex, SearchOption::MatchCase, SearchOption::WholeWord, SearchOption::InSelection];
+
+    fn name(&self) -> &'static str {
+        match self {
+            SearchOption::Regex => "Regex",
+            SearchOption::MatchCase => "Match case",
+            SearchOption::WholeWord => "Whole word",
+            SearchOption::InSelection => "In selection",
+        }
+    }
+}
+
+/// A match of the search, in bytes of the searched text
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String, // the replacement with the regex groups expanded, ex: `$1`
+}
+
+/// The state of the find/replace panel
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    pub query: String,
+    pub replacement: String,
+    pub use_regex: bool,
+    pub match_case: bool,
+    pub whole_word: bool,
+    pub in_selection: bool,
+    pub scope: Option<(Position, Position)>, // the selection when the panel was opened
+    compiled: RefCell<Option<CompiledQuery>>, // cached, compiled again when the query or its options change
+}
+
+/// The regex of a query, with what it was compiled from
+#[derive(Debug, Clone)]
+struct CompiledQuery {
+    pattern: String,
+    match_case: bool,
+    regex: Option<Regex>, // `None` for an invalid regex
+    is_line_bound: bool,
+}
+
+impl Search {
+    pub fn is_enabled(&self, option: SearchOption) -> bool {
+        match option {
+            SearchOption::Regex => self.use_regex,
+            SearchOption::MatchCase => self.match_case,
+            SearchOption::WholeWord => self.whole_word,
+            SearchOption::InSelection => self.in_selection,
+        }
+    }
+
+    pub fn toggle(&mut self, option: SearchOption) {
+        let value = !self.is_enabled(option);
+        match option {
+            SearchOption::Regex => self.use_regex = value,
+            SearchOption::MatchCase => self.match_case = value,
+            SearchOption::WholeWord => self.whole_word = value,
+            SearchOption::InSelection => self.in_selection = value,
+        }
+    }
+
+    /// The title of the menu item of `option`, with its state
+    pub fn get_option_title(&self, option: SearchOption) -> String {
+        format!("[{}] {}", if self.is_enabled(option) { "x" } else { " " }, option.name())
+    }
+
+    /// The bounds of the text to search in, the whole text when the search is not limited to the selection
+    pub fn get_scope(&self) -> Option<(Position, Position)> {
+        if self.in_selection { self.scope } else { Option::None }
+    }
+
+    /// `None` if the query is empty or is an invalid regex
+    fn get_regex(&self) -> Option<Regex> {
+        if self.query.is_empty() { return Option::None; }
+        self.get_compiled_query(|compiled| compiled.regex.clone())
+    }
+
+    /// Whether a match is always on a single line and is found the same way in the line alone, ex: not for `a\sb` or `\Aa`
+    /// The matches can then be found again on the edited lines only
+    pub fn is_line_bound(&self) -> bool {
+        self.get_compiled_query(|compiled| compiled.is_line_bound)
+    }
+
+    fn get_compiled_query<T>(&self, f: impl Fn(&CompiledQuery) -> T) -> T {
+        let pattern = if self.use_regex { self.query.clone() } else { regex::escape(&self.query) };
+        let mut compiled = self.compiled.borrow_mut();
+        if !compiled.as_ref().is_some_and(|compiled| compiled.pattern == pattern && compiled.match_case == self.match_case) {
+            let regex = RegexBuilder::new(&pattern)
+                .case_insensitive(!self.match_case)
+                .multi_line(true) // `^` and `$` match at the start and end of every line
+                .build()
+                .ok();
+            let is_line_bound = ParserBuilder::new().multi_line(true).build().parse(&pattern).is_ok_and(|hir| is_line_bound(&hir));
+            *compiled = Some(CompiledQuery { pattern, match_case: self.match_case, regex, is_line_bound });
+        }
+        f(compiled.as_ref().unwrap())
+    }
+
+    /// The bounds of all the non empty matches of the query in `text`, in bytes
+    pub fn find_all(&self, text: &str) -> Vec<(usize, usize)> {
+        let Some(regex) = self.get_regex() else { return vec![]; };
+        regex.find_iter(text).filter(|found| self.is_kept(text, found.start(), found.end())).map(|found| (found.start(), found.end())).collect()
+    }
+
+    /// All the non empty matches of the query in `text` with their replacement, slower than `find_all`
+    pub fn find_all_with_replacements(&self, text: &str) -> Vec<SearchMatch> {
+        let Some(regex) = self.get_regex() else { return vec![]; };
+        regex.captures_iter(text)
+            .filter_map(|captures| {
+                let found = captures.get(0)?;
+                if !self.is_kept(text, found.start(), found.end()) { return Option::None; }
+                let mut replacement = String::new();
+                if self.use_regex { captures.expand(&self.replacement, &mut replacement); } else { replacement = self.replacement.clone(); }
+                Some(SearchMatch { start: found.start(), end: found.end(), replacement })
+            })
+            .collect()
+    }
+
+    /// Whether the match between the bytes `start` and `end` of `text` is kept, it has to be a whole word with the option
+    fn is_kept(&self, text: &str, start: usize, end: usize) -> bool {
+        is_whole_graphemes(text, start, end) && (!self.whole_word || is_whole_word(text, start, end))
+    }
+}
+
+/// Whether the bytes between `start` and `end` hold whole grapheme clusters, ex: not the `e` of an `e` followed by a combining accent
+/// Only the matches made of whole graphemes are kept so a replacement can't split a cluster
+fn is_whole_graphemes(text: &str, start: usize, end: usize) -> bool {
+    let is_boundary = |byte: usize| GraphemeCursor::new(byte, text.len(), true).is_boundary(text, 0).unwrap_or(false);
+    start != end && is_boundary(start) && is_boundary(end)
+}
+
+/// Whether the match between the bytes `start` and `end` is not part of a longer word, ex: `#tag` in `#tag!` but not in `#tags`
+/// The graphemes around it are checked instead of wrapping the pattern in `\b`, which needs a word char inside the match, ex: for `(foo`
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |grapheme: Option<&str>| grapheme.is_some_and(|grapheme| grapheme.starts_with(|c: char| c.is_alphanumeric() || c == '_'));
+    !is_word(text[.. start].graphemes(true).next_back()) && !is_word(text[end ..].graphemes(true).next())
+}
+
+/// Whether `hir` can't match a line break nor look past the bounds of a line, ex: `\A` is only at the start of the text
+fn is_line_bound(hir: &Hir) -> bool {
+    match hir.kind() {
+        HirKind::Literal(Literal::Unicode(c)) => *c != '\n',
+        HirKind::Literal(Literal::Byte(byte)) => *byte != b'\n',
+        HirKind::Class(Class::Unicode(class)) => !class.iter().any(|range| range.start() <= '\n' && '\n' <= range.end()),
+        HirKind::Class(Class::Bytes(class)) => !class.iter().any(|range| range.start() <= b'\n' && b'\n' <= range.end()),
+        HirKind::Anchor(anchor) => matches!(anchor, Anchor::StartLine | Anchor::EndLine),
+        HirKind::Repetition(repetition) => is_line_bound(&repetition.hir),
+        HirKind::Group(group) => is_line_bound(&group.hir),
+        HirKind::Concat(hirs) | HirKind::Alternation(hirs) => hirs.iter().all(is_line_bound),
+        HirKind::Empty | HirKind::WordBoundary(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::search::{Search, SearchOption};
+
+    #[test]
+    fn search_options() {
+        let mut search = Search { query: "a.c".to_string(), ..Search::default() };
+        assert_eq!(search.find_all("abc a.c A.C"), vec![(4, 7), (8, 11)]);
+        search.toggle(SearchOption::MatchCase);
+        assert_eq!(search.find_all("abc a.c A.C"), vec![(4, 7)]);
+        search.toggle(SearchOption::Regex);
+        assert_eq!(search.find_all("abc a.c A.C"), vec![(0, 3), (4, 7)]);
+        search.query = "is".to_string();
+        search.toggle(SearchOption::WholeWord);
+        assert_eq!(search.find_all("this is"), vec![(5, 7)]);
+        search.query = "(".to_string();
+        assert!(search.find_all("(").is_empty()); // an invalid regex has no match
+    }
+
+    #[test]
+    fn whole_words_bounded_by_punctuation() {
+        let mut search = Search { query: "(foo".to_string(), whole_word: true, ..Search::default() };
+        assert_eq!(search.find_all("(foo) x(foo (foobar"), vec![(0, 4)]);
+        search.query = "#tag".to_string();
+        assert_eq!(search.find_all("#tag, #tags_x #tag"), vec![(0, 4), (14, 18)]);
+        search.query = "x+".to_string();
+        assert_eq!(search.find_all("x+ x+y e\u{301}x+"), vec![(0, 2)]); // an accented letter is part of the word
+    }
+
+    #[test]
+    fn line_bound_queries() {
+        let mut search = Search { query: "a\nb".to_string(), ..Search::default() };
+        assert!(!search.is_line_bound());
+        search.query = "^a.b$".to_string();
+        assert!(search.is_line_bound());
+        search.use_regex = true;
+        assert!(search.is_line_bound()); // `.` doesn't match a line break
+        for query in [r"a\sb", r"[^a]", r"\Aa", r"(?s)a.b"] {
+            search.query = query.to_string();
+            assert!(!search.is_line_bound(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn matches_keep_graphemes_whole() {
+        let search = Search { query: "e".to_string(), replacement: "x".to_string(), ..Search::default() };
+        assert_eq!(search.find_all("e\u{301}te"), vec![(4, 5)]); // the first `e` carries a combining accent
+        assert_eq!(search.find_all_with_replacements("e\u{301}te").len(), 1);
+    }
+
+    #[test]
+    fn regex_replacement() {
+        let search = Search { query: r"(\w+)@(\w+)".to_string(), replacement: "$2 at $1".to_string(), use_regex: true, ..Search::default() };
+        assert_eq!(search.find_all_with_replacements("me@home")[0].replacement, "home at me");
+    }
+}
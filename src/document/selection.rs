@@ -0,0 +1,40 @@
+use crate::document::position::Position;
+use crate::document::range::Range;
+use crate::document::range_trait::RangeTrait;
+
+/// The text selected by a cursor, from where the selection began to where the cursor is
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Selection {
+    range: Range,
+    is_block: bool, // the same columns are selected on every line between the start and the end
+}
+
+impl Selection {
+    pub fn start(&self) -> Option<Position> { self.range.get_real_start() }
+
+    pub fn end(&self) -> Option<Position> { self.range.get_real_end() }
+
+    pub fn set_start(&mut self, position: Position) { self.range.start(position) }
+
+    pub fn set_end(&mut self, position: Position) { self.range.end(position) }
+
+    pub fn set(&mut self, start: Position, end: Position) {
+        self.set_start(start);
+        self.set_end(end);
+    }
+
+    pub fn is_valid(&self) -> bool { self.range.is_valid() }
+
+    pub fn add(&mut self, range: Range) { self.range.add(range) }
+
+    pub fn is_block(&self) -> bool { self.is_block && self.is_valid() }
+
+    pub fn set_block(&mut self, is_block: bool) { self.is_block = is_block; }
+
+    pub fn reset(&mut self) {
+        self.range.reset();
+        self.is_block = false;
+    }
+
+    pub fn get_range(&self) -> Range { self.range }
+}
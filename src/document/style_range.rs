@@ -0,0 +1,204 @@
+use std::fmt::{Debug, Formatter};
+use crate::document::position::Position;
+use crate::document::range::{is_before, Range, shift_after_insert, shift_after_remove, translate, vector_max, vector_min};
+use crate::document::range_trait::RangeTrait;
+use crate::document::text_buffer::TextBuffer;
+
+/// The color of a styled text, converted to a color of the renderer when the text is laid out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl TextColor {
+    pub const BLACK: Self = Self { r: 0, g: 0, b: 0 };
+    pub const GREEN: Self = Self { r: 0, g: 255, b: 0 };
+}
+
+#[derive(Clone, Copy)]
+pub struct StyleRange {
+    pub color: TextColor,
+    pub bold: bool,
+    pub underline: bool,
+    pub strikethrough: bool, // barré
+    pub range: Range,
+}
+
+impl Default for StyleRange {
+    fn default() -> Self {
+        Self {
+            color: TextColor::BLACK,
+            bold: false,
+            underline: false,
+            strikethrough: false,
+            range: Range::default()
+        }
+    }
+}
+
+impl Debug for StyleRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let start_text = if let Some(start) = self.range.start { start.x.to_string() + "," + &start.y.to_string() } else { "None".to_owned() } ;
+        let end_text = if let Some(end) = self.range.end { end.x.to_string() + "," + &end.y.to_string() } else { "None".to_owned() } ;
+        write!(f, "Range : {} - {} \nbold: {}\nunderline: {}\nstrikethrough: {}\ncolor: {:?}", start_text, end_text, self.bold, self.underline, self.strikethrough, self.color)
+    }
+}
+
+impl PartialEq for StyleRange {
+    fn eq(&self, other: &Self) -> bool {
+        self.range.eq(&other.range)
+    }
+}
+
+impl StyleRange {
+    fn new_with_parameters(start: Position, end: Position, color: TextColor, bold: bool, underline: bool, strikethrough: bool) -> Self {
+        Self {
+            range: Range::new(start, end),
+            color,
+            bold,
+            underline,
+            strikethrough,
+        }
+    }
+
+    pub fn new_colored(range: Range, color: TextColor) -> Self {
+        Self {
+            range,
+            color,
+            bold: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    pub fn new_bold(range: Range) -> Self {
+        Self {
+            range,
+            color: TextColor::BLACK,
+            bold: true,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    pub fn new_underline(range: Range) -> Self {
+        Self {
+            range,
+            color: TextColor::BLACK,
+            bold: false,
+            underline: true,
+            strikethrough: false,
+        }
+    }
+
+    pub fn new_strikethrough(range: Range) -> Self {
+        Self {
+            range,
+            color: TextColor::BLACK,
+            bold: false,
+            underline: false,
+            strikethrough: true,
+        }
+    }
+
+    fn with_range(&self, start: Position, end: Position) -> Self { Self { range: Range::new(start, end), ..*self } }
+
+    /// Follow the text inserted between `start` and `end`, text typed inside the range gets its style
+    pub fn shift_after_insert(&mut self, start: Position, end: Position) {
+        let (Some(range_start), Some(range_end)) = (self.get_real_start(), self.get_real_end()) else { return; };
+        let new_end = if range_end == start { range_end } else { shift_after_insert(range_end, start, end) };
+        self.range = Range::new(shift_after_insert(range_start, start, end), new_end);
+    }
+
+    /// Follow the text removed between `start` and `end`, the range becomes invalid if all of its text is gone
+    pub fn shift_after_remove(&mut self, start: Position, end: Position) {
+        let (Some(range_start), Some(range_end)) = (self.get_real_start(), self.get_real_end()) else { return; };
+        self.range = Range::new(shift_after_remove(range_start, start, end), shift_after_remove(range_end, start, end));
+    }
+
+    /// The range following its text moved from `from` to `to`
+    pub fn translate(&self, from: Position, to: Position) -> Option<Self> {
+        Some(self.with_range(translate(self.get_real_start()?, from, to), translate(self.get_real_end()?, from, to)))
+    }
+
+    /// The part of the range that lies between `start` and `end`
+    pub fn intersect(&self, start: Position, end: Position) -> Option<Self> {
+        let (range_start, range_end) = (self.get_real_start()?, self.get_real_end()?);
+        let (part_start, part_end) = (vector_max(range_start, start), vector_min(range_end, end));
+        if is_before(part_start, part_end) { Some(self.with_range(part_start, part_end)) } else { Option::None }
+    }
+
+    /// Cut the range so each of the `lines` holds its own part, the line breaks around them lose their style
+    pub fn split_lines(&self, lines: &[u32], buffer: &TextBuffer) -> Vec<Self> {
+        let (Some(range_start), Some(range_end)) = (self.get_real_start(), self.get_real_end()) else { return vec![]; };
+        let mut parts = vec![];
+        let mut part_start = range_start;
+        for y in range_start.y ..= range_end.y {
+            if !lines.contains(&y) { continue; }
+            if y > 0 { parts.extend(self.intersect(part_start, Position::new(buffer.line_len(y - 1), y - 1))); }
+            parts.extend(self.intersect(Position::new(0, y), Position::new(buffer.line_len(y), y)));
+            part_start = Position::new(0, y + 1);
+        }
+        parts.extend(self.intersect(part_start, range_end));
+        parts
+    }
+}
+
+impl RangeTrait for StyleRange {
+    fn new(start: Position, end: Position,) -> Self {
+        Self {
+            range: Range::new(start, end),
+            color: TextColor::BLACK,
+            bold: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    fn get_range(&self) -> &Range { &self.range }
+
+    fn start(&mut self, position: Position) {
+        self.range.start(position)
+    }
+
+    fn end(&mut self, position: Position) {
+        self.range.end(position)
+    }
+
+    fn reset(&mut self) {
+        self.range.reset()
+    }
+
+    fn add(&mut self, other: StyleRange) {
+        self.range.add(other.range)
+    }
+
+    fn include(&self, other: &StyleRange) -> bool {
+        self.range.include(&other.range)
+    }
+
+    fn is_valid(&self) -> bool {
+        self.range.is_valid()
+    }
+
+    fn get_id(&self) -> String {
+        self.range.get_id()
+    }
+
+    fn get_real_start(&self) -> Option<Position> {
+        self.range.get_real_start()
+    }
+
+    fn get_real_end(&self) -> Option<Position> {
+        self.range.get_real_end()
+    }
+
+    fn get_ranges_from_drn_line(pattern: &str, lines: &Vec<&str>) -> Vec<Range> {
+        Range::get_ranges_from_drn_line(pattern, lines)
+    }
+
+    fn get_lines_index(&mut self, buffer: &TextBuffer) -> Vec<(u32, u32)> {
+        self.range.get_lines_index(buffer)
+    }}
\ No newline at end of file
@@ -0,0 +1,245 @@
+use std::cmp;
+use std::fmt::{Debug, Display, Formatter};
+
+use ropey::{Rope, RopeSlice};
+use ropey::iter::Chars;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::document::position::Position;
+use crate::document::range::{vector_max, vector_min};
+
+const CHAR_JUMP_LIST: [char; 12] = [' ', '_', '-', '/', '(', ')', '[', ']', '{', '}', '"', '\''];
+
+/// Number of extended grapheme clusters (user-perceived characters) of `text`
+pub fn get_grapheme_count(text: &str) -> u32 { text.graphemes(true).count() as u32 }
+
+/// The text of a document, stored in a rope so inserts, deletes and line lookups are O(log n)
+/// Positions are expressed as (column, line) like the cursor, where a column is a grapheme cluster
+#[derive(Clone, Default)]
+pub struct TextBuffer {
+    rope: Rope,
+}
+
+impl Debug for TextBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TextBuffer : {} lines, {} chars", self.line_count(), self.rope.len_chars())
+    }
+}
+
+impl From<&str> for TextBuffer {
+    fn from(text: &str) -> Self {
+        Self { rope: Rope::from_str(text) }
+    }
+}
+
+impl TextBuffer {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn line_count(&self) -> u32 { self.rope.len_lines() as u32 }
+
+    pub fn char_at(&self, char_index: usize) -> Option<char> { self.rope.get_char(char_index) }
+
+    /// An iterator over the chars from `char_index`, which can also go backward with `prev()`
+    pub fn chars_at(&self, char_index: usize) -> Chars<'_> { self.rope.chars_at(cmp::min(char_index, self.rope.len_chars())) }
+
+    /// The line `y` without its line break
+    fn get_line(&self, y: u32) -> RopeSlice<'_> {
+        let line = self.rope.line(y as usize);
+        let len = line.len_chars();
+        if len > 0 && line.char(len - 1) == '\n' { line.slice(.. len - 1) } else { line }
+    }
+
+    /// Number of grapheme clusters of the line `y`, without the line break
+    pub fn line_len(&self, y: u32) -> u32 {
+        if y >= self.line_count() { return 0; } // Prevent overflow
+        let line = self.get_line(y);
+        if is_ascii(&line) { line.len_chars() as u32 } else { get_grapheme_count(&line.to_string()) }
+    }
+
+    /// Convert the grapheme column `x` of the line `y` to a char offset in this line
+    fn get_char_offset(&self, x: u32, y: u32) -> usize {
+        let line = self.get_line(y);
+        if is_ascii(&line) { return cmp::min(x as usize, line.len_chars()); }
+        line.to_string().graphemes(true).take(x as usize).map(|g| g.chars().count()).sum()
+    }
+
+    /// Convert a char offset in the line `y` to a grapheme column, an offset inside a cluster goes after it
+    fn get_column(&self, char_offset: usize, y: u32) -> u32 {
+        let line = self.get_line(y);
+        if is_ascii(&line) { return cmp::min(char_offset, line.len_chars()) as u32; }
+        let mut offset = 0;
+        let mut column = 0;
+        for grapheme in line.to_string().graphemes(true) {
+            if offset >= char_offset { break; }
+            offset += grapheme.chars().count();
+            column += 1;
+        }
+        column
+    }
+
+    pub fn line_text(&self, y: u32) -> String {
+        if y >= self.line_count() { return String::new(); }
+        let mut text = self.rope.line(y as usize).to_string();
+        if text.ends_with('\n') { text.pop(); }
+        text
+    }
+
+    /// The grapheme cluster at `position`
+    pub fn grapheme_at(&self, position: Position) -> Option<String> {
+        if position.x >= self.line_len(position.y) { return Option::None; }
+        Some(self.get_text_between(position, Position::new(position.x + 1, position.y)))
+    }
+
+    /// Convert a (column, line) position to an absolute char index, clamped to the buffer
+    pub fn get_char_index(&self, position: Position) -> usize {
+        let y = cmp::min(position.y, self.line_count() - 1);
+        self.rope.line_to_char(y as usize) + self.get_char_offset(position.x, y)
+    }
+
+    pub fn get_position(&self, char_index: usize) -> Position {
+        let char_index = cmp::min(char_index, self.rope.len_chars());
+        let y = self.rope.char_to_line(char_index);
+        Position::new(self.get_column(char_index - self.rope.line_to_char(y), y as u32), y as u32)
+    }
+
+    /// Convert a (column, line) position to an absolute byte index in the text
+    pub fn get_byte_index(&self, position: Position) -> usize { self.rope.char_to_byte(self.get_char_index(position)) }
+
+    /// Convert an absolute byte index in the text to a (column, line) position
+    pub fn get_position_at_byte(&self, byte_index: usize) -> Position {
+        self.get_position(self.rope.byte_to_char(cmp::min(byte_index, self.rope.len_bytes())))
+    }
+
+    /// Convert a (column, line) position to a (char offset, line) one, used to store ranges in .drn files
+    pub fn to_char_position(&self, position: Position) -> Position {
+        let y = cmp::min(position.y, self.line_count() - 1);
+        Position::new(self.get_char_offset(position.x, y) as u32, y)
+    }
+
+    /// Convert a (char offset, line) position back to a (column, line) one
+    pub fn to_grapheme_position(&self, position: Position) -> Position {
+        let y = cmp::min(position.y, self.line_count() - 1);
+        Position::new(self.get_column(position.x as usize, y), y)
+    }
+
+    /// Insert `text` at `position` and return the position right after the inserted text
+    pub fn insert(&mut self, position: Position, text: &str) -> Position {
+        let index = self.get_char_index(position);
+        self.rope.insert(index, text);
+        self.get_position(index + text.chars().count())
+    }
+
+    /// Remove the text between two positions, in any order
+    pub fn remove(&mut self, start: Position, end: Position) {
+        let start_index = self.get_char_index(start);
+        let end_index = self.get_char_index(end);
+        self.rope.remove(cmp::min(start_index, end_index) .. cmp::max(start_index, end_index));
+    }
+
+    /// Replace the content of the line `y`, without touching its line break
+    pub fn replace_line(&mut self, y: u32, text: &str) {
+        self.remove(Position::new(0, y), Position::new(self.line_len(y), y));
+        self.insert(Position::new(0, y), text);
+    }
+
+    pub fn get_text_between(&self, start: Position, end: Position) -> String {
+        let start_index = self.get_char_index(vector_min(start, end));
+        let end_index = self.get_char_index(vector_max(start, end));
+        self.rope.slice(start_index .. end_index).to_string()
+    }
+
+    /// Find the first occurrence of `text` after `from`, wrapping around the end of the buffer
+    pub fn find(&self, text: &str, from: Position) -> Option<(Position, Position)> {
+        if text.is_empty() { return Option::None; }
+        let content = self.to_string();
+        let from_byte = self.rope.char_to_byte(self.get_char_index(from));
+        let byte_index = content[from_byte..].find(text).map(|i| i + from_byte).or_else(|| content.find(text))?;
+        let char_index = self.rope.byte_to_char(byte_index);
+        Some((self.get_position(char_index), self.get_position(char_index + text.chars().count())))
+    }
+
+    pub fn get_word_count(&self) -> u32 {
+        self.rope.lines().fold(0, |acc, line| acc + line.chars().fold((0, false), |(count, in_word), c| {
+            let is_word_char = !c.is_whitespace();
+            (if is_word_char && !in_word { count + 1 } else { count }, is_word_char)
+        }).0)
+    }
+
+    /// The first char of every grapheme cluster of the line `y`, enough to classify them
+    fn get_grapheme_chars(&self, y: u32) -> Vec<char> {
+        self.line_text(y).graphemes(true).map(|g| g.chars().next().unwrap()).collect()
+    }
+
+    /// Get the bounds of the word under `index` in the line `y`
+    pub fn get_word_at(&self, index: u32, y: u32) -> (u32, u32) {
+        let chars = self.get_grapheme_chars(y);
+        let mut start_index = cmp::min(index, chars.len() as u32);
+        let mut end_index = start_index;
+        while start_index > 0 && chars[start_index as usize - 1] != ' ' {
+            start_index -= 1;
+        }
+        while end_index < chars.len() as u32 && chars[end_index as usize] != ' ' {
+            end_index += 1;
+        }
+        (start_index, end_index)
+    }
+
+    /// Get the bounds of the word to jump to from `index` in the line `y`
+    pub fn get_next_jump(&self, index: u32, y: u32) -> (u32, u32) {
+        let chars = self.get_grapheme_chars(y);
+        let max_indices = chars.len() as u32;
+        let mut start_index = cmp::min(index, max_indices);
+        let mut end_index = start_index;
+        while start_index > 0 && CHAR_JUMP_LIST.contains(&chars[start_index as usize - 1]) { start_index -= 1 }
+        while end_index < max_indices && CHAR_JUMP_LIST.contains(&chars[end_index as usize]) { end_index += 1 }
+        while start_index > 0 && !CHAR_JUMP_LIST.contains(&chars[start_index as usize - 1]) { start_index -= 1; }
+        while end_index < max_indices && !CHAR_JUMP_LIST.contains(&chars[end_index as usize]) { end_index += 1; }
+        (start_index, end_index)
+    }
+}
+
+#[inline]
+fn is_ascii(text: &RopeSlice) -> bool { text.len_bytes() == text.len_chars() }
+
+impl Display for TextBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() { f.write_str(chunk)?; }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::document::position::Position;
+    use crate::document::text_buffer::TextBuffer;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut buffer = TextBuffer::from("hello\nworld");
+        let end = buffer.insert(Position::new(5, 0), " big\nnew");
+        assert_eq!(end, Position::new(3, 1));
+        assert_eq!(buffer.to_string(), "hello big\nnew\nworld");
+        assert_eq!(buffer.line_count(), 3);
+        buffer.remove(Position::new(3, 1), Position::new(5, 0)); // in any order
+        assert_eq!(buffer.to_string(), "hello\nworld");
+        assert_eq!(buffer.get_text_between(Position::new(1, 1), Position::new(2, 0)), "llo\nw");
+    }
+
+    #[test]
+    fn grapheme_columns() {
+        let buffer = TextBuffer::from("a\u{1F44D}\u{1F3FD}e\u{301}z");
+        assert_eq!(buffer.line_len(0), 4);
+        assert_eq!(buffer.grapheme_at(Position::new(2, 0)), Some("e\u{301}".to_string()));
+        assert_eq!(buffer.to_char_position(Position::new(3, 0)), Position::new(5, 0));
+        assert_eq!(buffer.to_grapheme_position(Position::new(5, 0)), Position::new(3, 0));
+        assert_eq!(buffer.to_grapheme_position(Position::new(2, 0)), Position::new(2, 0)); // inside a cluster
+    }
+
+    #[test]
+    fn find_wraps_around() {
+        let buffer = TextBuffer::from("foo\nbar foo");
+        assert_eq!(buffer.find("foo", Position::new(1, 0)), Some((Position::new(4, 1), Position::new(7, 1))));
+        assert_eq!(buffer.find("foo", Position::new(5, 1)), Some((Position::new(0, 0), Position::new(3, 0))));
+        assert_eq!(buffer.find("baz", Position::ZERO), None);
+    }
+}
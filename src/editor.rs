@@ -1,5 +1,4 @@
 use std::{cmp, env, fs};
-use std::any::TypeId;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::path::{Path, PathBuf};
@@ -7,10 +6,9 @@ use std::time::Instant;
 
 use speedy2d::color::Color;
 use speedy2d::dimen::Vector2;
-use speedy2d::font::TextAlignment;
 use speedy2d::Graphics2D;
 use speedy2d::shape::Rectangle;
-use speedy2d::window::{ModifiersState, UserEventSender, VirtualKeyCode};
+use speedy2d::window::{ModifiersState, MouseScrollDistance, UserEventSender, VirtualKeyCode};
 
 use clipboard::ClipboardProvider;
 use clipboard::ClipboardContext;
@@ -20,27 +18,46 @@ use ifmt::iformat;
 
 use serde_yaml;
 
-use crate::cursor::{Cursor, CURSOR_OFFSET_X};
+use crate::cursor::{Cursor, CURSOR_OFFSET_X, CURSOR_WIDTH};
 use crate::camera::Camera;
 use crate::contextual_menu::{ContextualMenu, MenuItem};
 use crate::{Animation, EditorEvent, FocusElement, MenuId};
-use crate::style_range::StyleRange;
+use crate::document::{parse_auto_pairs, Alignment, Change, Document, Modifiers, DEFAULT_AUTO_PAIRS};
+use crate::document::brackets::find_bracket_pair;
+use crate::document::file_format::{is_text, Encoding, FileFormat, LineEnding};
+use crate::document::go_to::GoTo;
+use crate::document::indentation::Indentation;
+use crate::document::position::Position;
+use crate::document::range::{is_before, vector_max, Range};
+use crate::document::range_trait::RangeTrait;
+use crate::document::search::SearchOption;
+use crate::document::style_range::{StyleRange, TextColor};
 use crate::menu_actions::MenuAction;
 use crate::font::Font;
-use crate::line::Line;
-use crate::range::Range;
-use crate::selection::Selection;
+use crate::line::{get_position_at, LayoutSettings, Line, WrapMode};
+use crate::selection::{Selection, SelectionUnit};
 use crate::editable::Editable;
-use crate::range_trait::RangeTrait;
 use crate::stats::Stats;
+use crate::event_sender::send_event;
+use crate::scrollbar::{Orientation, Scrollbar, SCROLLBAR_WIDTH};
+use crate::render_helper::draw_rounded_line;
 
 pub const EDITOR_PADDING: f32 = 10.;
 pub const EDITOR_OFFSET_TOP: f32 = 55.;
-
-
+const AUTO_SCROLL_SPEED: f32 = 0.02; // px scrolled by ms for every px between the mouse and the text area
+const WHEEL_SCROLL_LINES: f32 = 3.; // lines scrolled by a notch of the mouse wheel
+/// Whether the typographic substitutions are displayed when there is no preference for the type of the file
+const DEFAULT_USE_SUBSTITUTIONS: bool = true;
+
+/// The rendering of a `Document`: the layout of its lines, the display of its cursors, the camera, the menus and the files
+/// Without event sender (ex: in tests) the editor runs headless: nothing is animated, the window events are dropped
+/// and the clipboard is kept in memory. It still loads its fonts from `./resources` to lay out the text
 pub struct Editor {
-    pub lines: Vec<Line>,
-    pub cursor: Cursor,
+    pub document: Document, // the text, its cursors, styles and history, every edit is made on it
+    pub lines: Vec<Line>, // the layout of each line of the buffer
+    pub cursor: Cursor, // the display of the main cursor, followed by the camera
+    pub selection: Selection, // the display of the main selection
+    secondary_cursors: Vec<(Cursor, Selection)>, // the display of the secondary cursors, in the order of the document ones
     pub camera: Camera,
     pub offset: Vector2<f32>,
     pub padding: f32,
@@ -49,12 +66,22 @@ pub struct Editor {
     pub modifiers : ModifiersState,
     pub filepath: Option<String>,
     pub event_sender: Option<UserEventSender<EditorEvent>>,
-    pub selection: Selection,
-    pub style_buffer: Vec<StyleRange>, // a buffer that keeps track of every style in the document
     pub menu: ContextualMenu,
     pub cached_prefs: Option<serde_yaml::Value>,
     pub stats: Stats,
     pub should_edit_file: bool, // so the input internal editor does not trigger file specific events
+    pub use_substitutions: bool, // display typographic substitutions, ex: `->` as an arrow, the buffer keeps the typed text
+    is_camera_free: bool, // the camera was scrolled away from the cursor, it follows the cursor again once it moves
+    content_width: Option<f32>, // the width of the widest line, `None` when the lines have to be measured again
+    scrollbar_drag: Option<(Orientation, f32)>, // the scrollbar grabbed and where its thumb is grabbed
+    drag_unit: SelectionUnit,
+    drag_anchor: (Position, Position), // the word or the line selected by the click that started the drag
+    drop_position: Option<Position>, // where the dragged selection would be dropped, `None` when no text is dragged
+    bracket_pair: Option<(Position, Option<(Position, Position)>)>, // the cursor and the bracket pair next to it, `None` when the text changed
+    pub wrap_mode: WrapMode,
+    layout_settings: LayoutSettings, // the settings of the last layout, every line is laid out again when they change
+    pub clipboard: String, // used when there is no system clipboard and by a headless editor
+    is_dirty: bool, // the state of the document shown by a star in the title bar
 }
 
 impl Editor {
@@ -66,7 +93,9 @@ impl Editor {
         )));
         let system_font = Rc::new(RefCell::new(Font::new(&Self::get_file_path("./resources/font/Roboto-Regular.ttf"), width, height)));
         Self {
+            document: Document::new(),
             cursor: Cursor::new(0, 0, Rc::clone(&font)),
+            secondary_cursors: vec![],
             camera: Camera::new(width, height, offset, padding),
             lines: vec![Line::new(Rc::clone(&font))],
             selection: Selection::new(Rc::clone(&font)),
@@ -74,167 +103,80 @@ impl Editor {
             modifiers: ModifiersState::default(),
             filepath: Option::None,
             event_sender: Option::None,
-            style_buffer: vec![],
             menu: ContextualMenu::new(system_font),
             cached_prefs: Option::None,
             offset,
             padding,
             font,
             stats: Stats::default(),
-            should_edit_file: true
+            should_edit_file: true,
+            use_substitutions: DEFAULT_USE_SUBSTITUTIONS,
+            is_camera_free: false,
+            content_width: Option::None,
+            scrollbar_drag: Option::None,
+            drag_unit: SelectionUnit::Char,
+            drag_anchor: (Position::ZERO, Position::ZERO),
+            drop_position: Option::None,
+            bracket_pair: Option::None,
+            wrap_mode: WrapMode::Window,
+            layout_settings: LayoutSettings::default(),
+            clipboard: String::new(),
+            is_dirty: false,
         }
     }
 }
 
+impl From<&ModifiersState> for Modifiers {
+    fn from(state: &ModifiersState) -> Self {
+        Self { shift: state.shift(), ctrl: state.ctrl(), alt: state.alt(), logo: state.logo() }
+    }
+}
+
 impl Editable for Editor {
     fn add_char(&mut self, c: String) {
         if self.modifiers.logo() {
             let chars: Vec<char> = c.chars().collect();
             return self.shortcut(chars[0]);
         }
-        // matching template
-        let mut after = "";
-        for template in [("(", ")"), ("[", "]"), ("{", "}"), ("\"", "\"")] {
-            if c == template.0 { after = template.1; break }
-        }
-        if after == "" { self.delete_selection(); }
-        let pos = if self.selection.is_valid() { self.selection.start().unwrap() } else { Vector2::new(self.cursor.x, self.cursor.y) };
-        self.get_current_buffer().insert(pos.x as usize, c);
-        if after != "" {
-            let after_pos = if self.selection.is_valid() { self.selection.end().unwrap() } else { Vector2::new(self.cursor.x, self.cursor.y) };
-            self.lines[after_pos.y as usize].buffer.insert(after_pos.x as usize + 1, after.into());
-        }
-        self.set_dirty(true);
-        self.move_cursor_relative(1, 0);
-        self.selection.reset();
+        self.edit(|document| document.add_char(&c));
     }
 
     fn delete_char(&mut self) {
-        if self.modifiers.alt() || self.modifiers.logo()  {
-            self.begin_selection();
-            self.move_cursor_relative(-1, 0);
-            self.end_selection();
-        }
-        self.set_dirty(true);
-        if self.selection.is_valid() {
-            self.delete_selection();
-            return;
-        }
-        let pos = self.cursor.x as i32;
-        let row = self.cursor.y;
-        if pos == 0 {
-            if row == 0 { return; } // The first line should never be deleted
-            let buffer = self.get_current_buffer().clone();
-            let previous_buffer = &mut self.lines[row as usize - 1].buffer;
-            let previous_line_buffer_previous_size = previous_buffer.len() as u32;
-            buffer.iter().for_each(|c| previous_buffer.push(c.clone()));
-            self.cursor.move_to(previous_line_buffer_previous_size, self.cursor.y - 1);
-            self.lines.remove(row as usize);
-        } else {
-            let buffer = self.get_current_buffer();
-            assert!(pos <= buffer.len() as i32);
-            // Auto delete the matching template char if there are next to each other - ex: ""
-            if buffer.len() as i32 > pos && buffer.get(pos as usize - 1) == buffer.get(pos as usize)
-                && ["(", "[", "{", "\""].contains(&buffer.get(pos as usize - 1).unwrap().as_str()) {
-                buffer.remove(pos as usize);
-            }
-            buffer.remove(pos as usize - 1);
-            self.move_cursor_relative(-1, 0);
-        }
-        self.selection.reset();
+        self.edit(Document::delete_char);
         self.update_text_layout();
     }
 
     fn handle_key(&mut self, keycode: VirtualKeyCode) {
         let ctrl_alt = self.modifiers.logo() && self.modifiers.alt();
+        let add_cursor = self.modifiers.ctrl() && self.modifiers.alt();
 
         match keycode {
-            VirtualKeyCode::Right => if ctrl_alt { self.set_line_alignment(TextAlignment::Right) } else { self.move_cursor_relative(1, 0) },
-            VirtualKeyCode::Left => if ctrl_alt { self.set_line_alignment(TextAlignment::Left) } else { self.move_cursor_relative(-1, 0) },
-            VirtualKeyCode::Up => if ctrl_alt { self.set_line_alignment(TextAlignment::Center) } else { self.move_cursor_relative(0, -1) },
-            VirtualKeyCode::Down => self.move_cursor_relative(0, 1),
+            VirtualKeyCode::Right => if ctrl_alt { self.edit(|document| document.set_line_alignment(Alignment::Right)) } else { self.move_cursor_relative(1, 0) },
+            VirtualKeyCode::Left => if ctrl_alt { self.edit(|document| document.set_line_alignment(Alignment::Left)) } else { self.move_cursor_relative(-1, 0) },
+            VirtualKeyCode::Up => if ctrl_alt { self.edit(|document| document.set_line_alignment(Alignment::Center)) } else if add_cursor { self.edit(|document| document.add_cursor_vertically(-1)) } else { self.move_cursor_relative(0, -1) },
+            VirtualKeyCode::Down => if add_cursor { self.edit(|document| document.add_cursor_vertically(1)) } else { self.move_cursor_relative(0, 1) },
             VirtualKeyCode::Backspace => self.delete_char(),
             VirtualKeyCode::Delete => { self.move_cursor_relative(1, 0); self.delete_char(); },
-            VirtualKeyCode::Return => if self.modifiers.alt() { self.toggle_ai_contextual_menu() } else { self.new_line() },
-            VirtualKeyCode::Escape => self.menu.close(),
-            VirtualKeyCode::Tab => if self.modifiers.alt() { self.menu.open() } else { self.add_text("    ") },
+            VirtualKeyCode::Return => if self.modifiers.alt() { self.toggle_ai_contextual_menu() } else { self.edit(Document::new_line) },
+            VirtualKeyCode::Escape => if self.menu.is_visible { self.menu.close() } else { self.edit(Document::remove_secondary_cursors) },
+            VirtualKeyCode::Tab => if self.modifiers.alt() { self.menu.open() } else {
+                let outdent = self.modifiers.shift();
+                self.edit(|document| document.indent_at_cursors(outdent))
+            },
             _ => { return; },
         }
         self.update_text_layout();
     }
 
-    fn move_cursor(&mut self, position: Vector2<u32>) {
-        assert!(!self.lines.is_empty());
-        let pos = self.get_valid_cursor_position(position);
-        if pos.x != self.cursor.x || pos.y != self.cursor.y {
-            self.cursor.move_to(pos.x, pos.y);
-        }
+    fn move_cursor(&mut self, position: Position) {
+        self.edit(|document| document.move_cursor(position));
         self.update_camera();
     }
 
     fn move_cursor_relative(&mut self, rel_x: i32, rel_y: i32) {
-        let max_y = self.lines.len() as i32 - 1;
-        let mut new_x = (self.cursor.x as i32 + rel_x) as i32;
-        let mut new_y = (self.cursor.y as i32 + rel_y).clamp(0, max_y);
-
-        if self.modifiers.shift() && self.selection.start().is_none() {
-            self.selection.set_start(Vector2::new(self.cursor.x, self.cursor.y));
-        }
-
-        if self.modifiers.alt() {  // Move to the previous/next word
-            let (start, end) = self.lines[self.cursor.y as usize].get_next_jump(self.cursor.x);
-            if rel_x < 0 && start != self.cursor.x  {
-                new_x = start as i32;
-            } else if rel_x > 0 && end != self.cursor.x  {
-                new_x = end as i32;
-            }
-        } else if self.modifiers.logo() { // Move to the start/end of the line/file
-            if self.modifiers.ctrl() {
-                self.switch_lines(rel_y)
-            } else {
-                if rel_x < 0  { new_x = 0; }
-                else if rel_x > 0 { new_x = self.lines[self.cursor.y as usize].buffer.len() as i32; }
-                if rel_y < 0 { new_y = 0; }
-                else if rel_y > 0 { new_y = self.lines.len() as i32 - 1; }
-            }
-        }
-
-        if self.selection.is_valid() && !self.modifiers.shift() && !(self.modifiers.logo() && self.modifiers.ctrl()) { // go to the start/end of the selection
-            if rel_x > 0 || rel_y > 0 {
-                self.move_cursor(self.selection.end().unwrap());
-                self.selection.reset();
-                return;
-            } else if rel_x < 0 || rel_y < 0 {
-                self.move_cursor(self.selection.start().unwrap());
-                self.selection.reset();
-                return;
-            }
-        }
-
-        if new_x < 0 {  // Go to line before
-            if self.cursor.y == 0 { return; }
-            let previous_line_buffer_size = self.lines[self.cursor.y as usize - 1].buffer.len() as u32;
-            self.cursor.move_to(previous_line_buffer_size, self.cursor.y - 1);
-        } else if new_x as usize > self.get_current_buffer().len() { // Go to line after
-            if self.cursor.y as usize >= self.lines.len() - 1 {return; }
-            self.cursor.move_to(0, self.cursor.y + 1);
-        } else {
-            // Classic move inside a line
-            // Check if x if inside new_y buffer limits
-            let new_buffer_len = self.lines[new_y as usize].buffer.len() as i32;
-            if new_x >= new_buffer_len {
-                self.cursor.move_to(new_buffer_len as u32, new_y as u32);
-            } else {
-                self.cursor.move_to(new_x as u32, new_y as u32);
-            }
-        }
-        // Update selection
-        if self.modifiers.shift() {
-            self.selection.set_end(Vector2::new(self.cursor.x, self.cursor.y));
-        } else if (rel_x.abs() > 0 || rel_y.abs() > 0) && self.selection.is_valid() && !(self.modifiers.logo() && self.modifiers.ctrl()) {
-            self.selection.reset();
-        }
-        self.update_camera();
+        // The vertical moves go by the rows of the laid out lines
+        self.document.move_cursor_relative(rel_x, rel_y, &self.lines);
+        self.sync_document();
     }
 
     fn shortcut(&mut self, c: char) {
@@ -244,133 +186,159 @@ impl Editable for Editor {
             'o' => self.load(),
             'u' => self.underline(),
             'b' => self.bold(),
+            'B' => self.edit(Document::toggle_block_selection),
             'c' => self.copy(),
             'v' => self.paste(),
-            'x' => { self.copy(); self.delete_selection() },
+            'x' => self.cut(),
             'a' => self.select_all(),
+            'z' => self.edit(Document::undo),
+            'Z' => self.edit(Document::redo),
             'l' => self.select_current_line(),
             'L' => { self.select_current_line(); self.delete_selection() },
             'w' | 'q' => self.quit(),
-            'd' => self.select_current_word(),
-            'D' => self.duplicate_line(),
+            'd' => self.edit(Document::select_next_occurrence),
+            'D' => self.edit(Document::duplicate_line),
             '+' | '=' => self.increase_font_size(),
             '-' => self.decrease_font_size(),
             'n' => self.new_file_popup(),
             'N' => self.new_file("new-file.txt"),
             'i' => self.toggle_stats_popup(),
-            'r' => self.find_next(),
+            'f' => self.toggle_search_menu(),
+            'r' => self.find_next_match(),
+            'R' => self.find_previous_match(),
             'p' => self.print_dir(),
             'P' => self.toggle_ai_contextual_menu(),
-            'k' => self.colorize(Color::GREEN),
+            'k' => self.edit(|document| document.colorize(TextColor::GREEN)),
+            't' => self.toggle_substitutions(),
+            'e' => self.toggle_file_format_popup(),
+            'g' => self.toggle_go_to_popup(),
+            'm' => self.edit(Document::jump_to_matching_bracket),
+            '[' => self.edit(|document| document.indent_lines_at_cursors(true)),
+            ']' => self.edit(|document| document.indent_lines_at_cursors(false)),
+            'M' => self.edit(Document::select_inside_brackets),
             _ => {}
         }
     }
 
-    fn begin_selection(&mut self) {
-        self.selection.set_start((self.cursor.x, self.cursor.y).into());
-    }
-
-    fn end_selection(&mut self) {
-        self.selection.set_end((self.cursor.x, self.cursor.y).into());
-    }
-
     fn update_selection(&mut self, position: Vector2<f32>) {
         let mouse_position = self.get_mouse_position_index(position);
-        if let Some(end) = &self.selection.end() {
-            if mouse_position == *end { return; }
-        }
-        self.selection.set_end(mouse_position);
-        self.move_cursor(mouse_position);
+        if self.drag_unit != SelectionUnit::Char { return self.extend_selection_by_unit(mouse_position); }
+        if self.document.selection.end() == Some(mouse_position) { return; }
+        self.edit(|document| {
+            document.selection.set_end(mouse_position);
+            document.move_cursor(mouse_position);
+        });
     }
 
     fn delete_selection(&mut self) {
-        if self.selection.is_valid() {
-            let initial_i = cmp::min(self.selection.start().unwrap().y, self.selection.end().unwrap().y) as usize;
-            let lines_indices = self.selection.get_lines_index(&self.lines);
-            for (i, indices) in lines_indices.iter().enumerate() {
-                let start = cmp::min(indices.0, indices.1) as usize;
-                let end = cmp::max(indices.0, indices.1) as usize;
-                let _ = &self.lines[initial_i + i].buffer.drain(start .. end);
-            }
-            for i in 0 .. lines_indices.len() {
-                let index = initial_i + lines_indices.len() - i - 1;
-                if self.lines[index].buffer.is_empty() && self.lines.len() > 1 {
-                    self.lines.remove(index);
-                }
-            }
-            let selection_start = self.selection.start().unwrap();
-            self.move_cursor(Vector2::new(selection_start.x, selection_start.y));
-            self.selection.reset();
-        }
+        self.edit(Document::delete_selection);
     }
 
-    fn get_mouse_position_index(&mut self, position: Vector2<f32>) -> Vector2<u32> {
-        let pos = Vector2::new(
-            ((position.x as f32 + self.camera.computed_x()) / self.font.borrow().char_width + 0.5) as u32,
-            ((position.y as f32 + self.camera.computed_y()) / self.font.borrow().char_height) as u32,
-        );
-        self.get_valid_cursor_position(pos)
-    }
-
-    fn get_valid_cursor_position(&mut self, position: Vector2<u32>) -> Vector2<u32> {
-        let max_y = self.lines.len() as u32 - 1;
-        let y = cmp::min(position.y, max_y);
-        let line = &self.lines[y as usize];
-        let x =  (position.x as i32 - (line.alignment_offset / self.font.borrow().char_width + 0.5) as i32).clamp(0, line.buffer.len() as i32) as u32;
-        Vector2::new(x, y)
+    fn get_mouse_position_index(&mut self, position: Vector2<f32>) -> Position {
+        self.get_position_at(position + self.camera.position())
     }
 
     fn select_current_word(&mut self) {
-        let (start, end) = self.lines[self.cursor.y as usize].get_word_at(self.cursor.x);
-        self.selection.set(
-            Vector2::new(start, self.cursor.y),
-            Vector2::new(end, self.cursor.y),
-        )
+        self.edit(Document::select_current_word);
     }
 
     fn select_all(&mut self) {
-        let last_line_length = self.lines.last().unwrap().buffer.len() as u32;
-        self.selection.set(Vector2::ZERO, Vector2::new(last_line_length, self.lines.len() as u32 - 1));
+        self.edit(Document::select_all);
     }
 
     fn select_current_line(&mut self) {
-        let line = &self.lines[self.cursor.y as usize];
-        let line_selection = Range::new(
-            Vector2::new(0, self.cursor.y),
-            Vector2::new(line.buffer.len() as u32, self.cursor.y)
-        );
-        self.selection.add(line_selection);
-        self.move_cursor(Vector2::new(0, self.cursor.y + 1))
+        self.edit(Document::select_current_line);
     }
 
     fn copy(&mut self) {
-        if !self.selection.is_valid() { return; }
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        let selection_text = self.get_selected_text();
-        ctx.set_contents(selection_text).unwrap();
+        if let Some(text) = self.document.copy() { self.set_clipboard_contents(text); }
     }
 
     fn paste(&mut self) {
-        if self.selection.is_valid() { self.delete_selection(); }
-        let mut ctx: ClipboardContext = ClipboardProvider::new().unwrap();
-        let clipboard_content = ctx.get_contents().unwrap();
-        if clipboard_content.is_empty() { return; }
-        let mut lines = clipboard_content.split('\n').filter(|c| *c != "");
-        let mut text = lines.next();
-        while text.is_some() {
-            self.lines[self.cursor.y as usize].add_text(text.unwrap());
-            self.move_cursor_relative(text.unwrap().len() as i32, 0);
-            text = lines.next();
-            if text.is_some() {
-                self.lines.push(Line::new(Rc::clone(&self.font)));
-                self.move_cursor_relative(0, 1);
-            }
-        }
-        self.set_dirty(true);
+        let clipboard_content = self.get_clipboard_contents();
+        self.edit(|document| document.paste(&clipboard_content));
     }
 }
 
 impl Editor {
+    /// Apply `action` to the document, then show its result
+    pub fn edit(&mut self, action: impl FnOnce(&mut Document)) {
+        action(&mut self.document);
+        self.sync_document();
+    }
+
+    /// Show the last edits of the document: the changed lines are laid out again on the next `update_text_layout`
+    /// and the cursors and the selections move to their new place
+    fn sync_document(&mut self) {
+        let has_changes = self.apply_document_changes();
+        let has_moved = self.document.cursor != Position::new(self.cursor.x, self.cursor.y);
+        self.cursor.sync(self.document.cursor);
+        self.selection.sync(&self.document.selection);
+        self.secondary_cursors.truncate(self.document.secondary_cursors.len());
+        for secondary_cursor in &self.document.secondary_cursors[self.secondary_cursors.len() ..] {
+            let mut cursor = Cursor::new(secondary_cursor.cursor.x, secondary_cursor.cursor.y, Rc::clone(&self.font));
+            cursor.event_sender = self.event_sender.clone();
+            let mut selection = Selection::new(Rc::clone(&self.font));
+            selection.event_sender = self.event_sender.clone();
+            self.secondary_cursors.push((cursor, selection));
+        }
+        for ((cursor, selection), secondary_cursor) in self.secondary_cursors.iter_mut().zip(&self.document.secondary_cursors) {
+            cursor.sync(secondary_cursor.cursor);
+            selection.sync(&secondary_cursor.selection);
+        }
+        if has_moved || has_changes { self.update_camera(); }
+        if self.document.is_dirty() != self.is_dirty { self.set_dirty(self.document.is_dirty()); }
+    }
+
+    /// Replay the changes of the lines of the document on their layout, return `false` if there is none
+    fn apply_document_changes(&mut self) -> bool {
+        let changes = self.document.take_changes();
+        let mut rows_changed = false;
+        if changes.iter().any(|change| !matches!(change, Change::Restyle(..))) { self.bracket_pair = Option::None; }
+        for change in &changes {
+            match *change {
+                Change::Insert(start, end) => {
+                    self.lines[start.y as usize].invalidate();
+                    for i in 0 .. end.y - start.y {
+                        self.lines.insert((start.y + i + 1) as usize, Line::new(Rc::clone(&self.font)));
+                    }
+                    rows_changed |= end.y != start.y;
+                },
+                Change::Remove(start, end) => {
+                    let content_width = self.content_width;
+                    if self.lines.drain((start.y + 1) as usize ..= end.y as usize).any(|line| content_width.is_none_or(|width| line.get_width() >= width)) {
+                        self.content_width = Option::None; // the widest line is gone
+                    }
+                    self.lines[start.y as usize].invalidate();
+                    rows_changed |= end.y != start.y;
+                },
+                Change::SwapLines(a, b) => {
+                    self.lines.swap(a as usize, b as usize);
+                    self.lines[a as usize].invalidate();
+                    self.lines[b as usize].invalidate();
+                },
+                Change::Restyle(first, last) => {
+                    let last_line = self.lines.len() as u32 - 1;
+                    if first > last_line { continue; }
+                    for line in &mut self.lines[first as usize ..= cmp::min(last, last_line) as usize] { line.invalidate(); }
+                },
+                Change::Reset(line_count) => {
+                    self.lines = (0 .. line_count).map(|_| Line::new(Rc::clone(&self.font))).collect();
+                    self.content_width = Option::None;
+                    rows_changed = true;
+                },
+            }
+        }
+        if rows_changed { self.update_row_offsets(); }
+        !changes.is_empty()
+    }
+
+    /// Keep the modifiers for the shortcuts of the editor and the edits of the document
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.document.modifiers = Modifiers::from(&modifiers);
+        self.modifiers = modifiers;
+    }
+
     fn quit(&mut self) {
         if let Some(_filepath) = &mut self.filepath {  } else { self.filepath = Some("newfile.txt".into()); }
         self.save();
@@ -383,6 +351,32 @@ impl Editor {
         self.selection.event_sender = es.clone();
         self.camera.event_sender = es.clone();
         self.menu.event_sender = es.clone();
+        for (cursor, selection) in &mut self.secondary_cursors {
+            cursor.event_sender = es.clone();
+            selection.event_sender = es.clone();
+        }
+    }
+
+    pub fn add_cursor(&mut self, position: Position) {
+        self.edit(|document| document.add_cursor(position));
+    }
+
+    /// Begin a block selection at `position`, ex: on alt + drag
+    pub fn begin_block_selection(&mut self, position: Position) {
+        self.drag_unit = SelectionUnit::Char;
+        self.edit(|document| document.begin_block_selection(position));
+    }
+
+    /// A headless editor never touches the system clipboard, so a scripted session doesn't depend on it or overwrite it
+    fn set_clipboard_contents(&mut self, text: String) {
+        let is_copied = self.event_sender.is_some()
+            && ClipboardContext::new().and_then(|mut ctx: ClipboardContext| ctx.set_contents(text.clone())).is_ok();
+        if !is_copied { self.clipboard = text; }
+    }
+
+    fn get_clipboard_contents(&self) -> String {
+        if self.event_sender.is_none() { return self.clipboard.clone(); }
+        ClipboardContext::new().and_then(|mut ctx: ClipboardContext| ctx.get_contents()).unwrap_or_else(|_| self.clipboard.clone())
     }
 
     fn send_event(&self, event: EditorEvent) {
@@ -390,10 +384,12 @@ impl Editor {
             EditorEvent::SetDirty(_, _) | EditorEvent::LoadFile(_) => if !self.should_edit_file { return; }
             _ => {}
         }
-        self.event_sender.as_ref().unwrap().send_event(event).unwrap();
+        send_event(&self.event_sender, event);
     }
 
     pub fn set_dirty(&mut self, dirty: bool) { // Sert the editor in a "unsave" state --> display a star in the title bar
+        self.document.set_dirty(dirty);
+        self.is_dirty = dirty;
         let path = self.filepath.clone().unwrap_or(String::from(""));
         self.send_event(EditorEvent::SetDirty(path, dirty)); // Set the editor dirty
     }
@@ -411,132 +407,169 @@ impl Editor {
     pub fn on_resize(&mut self, size: Vector2<u32>) {
         self.system_font.borrow_mut().on_resize(size);
         self.camera.on_resize(size);
-        self.font.borrow_mut().on_resize(size);
-    }
-
-    fn get_valid_cursor_position(&mut self, position: Vector2<u32>) -> Vector2<u32> {
-        let max_y = self.lines.len() as u32 - 1;
-        let y = cmp::min(position.y, max_y);
-        let line = &self.lines[y as usize];
-        let x =  (position.x as i32 - (line.alignment_offset / self.font.borrow().char_width + 0.5) as i32).clamp(0, line.buffer.len() as i32) as u32;
-        Vector2::new(x, y)
+        // The text is displayed inside the padding, like in `set_offset`
+        self.font.borrow_mut().editor_size = Vector2::new(
+            size.x as f32 - self.offset.x - self.padding * 2.,
+            size.y as f32 - self.offset.y - self.padding * 2.,
+        );
     }
 
     pub fn update_camera(&mut self) {
+        self.is_camera_free = false;
+        let (char_width, char_height) = (self.font.borrow().char_width, self.font.borrow().char_height);
+        let line = &self.lines[cmp::min(self.cursor.y as usize, self.lines.len() - 1)];
+        let cursor_position = line.to_visual_position(self.cursor.real_x());
         // Horizontal Scroll
-        if self.camera.get_cursor_x_with_offset(&self.cursor) < self.camera.computed_x() + self.camera.safe_zone_size {
-            self.camera.move_x(self.camera.get_cursor_x_with_offset(&self.cursor) - self.camera.computed_x() - self.camera.safe_zone_size);
-        } else if self.padding + self.cursor.real_x() - self.camera.computed_x() > self.camera.width - self.camera.safe_zone_size {
-            self.camera.move_x(self.padding + self.cursor.real_x() - self.camera.computed_x() - self.camera.width + self.camera.safe_zone_size);
+        let cursor_x = self.camera.get_cursor_x_with_offset(cursor_position.x, char_width);
+        if cursor_x < self.camera.computed_x() + self.camera.safe_zone_size {
+            self.camera.move_x(cursor_x - self.camera.computed_x() - self.camera.safe_zone_size);
+        } else if self.padding + cursor_position.x - self.camera.computed_x() > self.camera.width - self.camera.safe_zone_size {
+            self.camera.move_x(self.padding + cursor_position.x - self.camera.computed_x() - self.camera.width + self.camera.safe_zone_size);
         }
         // Vertical Scroll
-        if self.camera.get_cursor_y_with_offset(&self.cursor) < self.camera.computed_y() + self.camera.safe_zone_size {
-            self.camera.move_y(self.camera.get_cursor_y_with_offset(&self.cursor) - self.camera.computed_y() - self.camera.safe_zone_size)
-        } else if self.padding + self.cursor.real_y() - self.camera.computed_y() > self.camera.height - self.camera.safe_zone_size {
-            self.camera.move_y(self.padding + self.cursor.real_y() - self.camera.computed_y() - self.camera.height + self.camera.safe_zone_size)
+        let cursor_y = self.camera.get_cursor_y_with_offset(cursor_position.y, char_height);
+        if cursor_y < self.camera.computed_y() + self.camera.safe_zone_size {
+            self.camera.move_y(cursor_y - self.camera.computed_y() - self.camera.safe_zone_size)
+        } else if self.padding + cursor_position.y - self.camera.computed_y() > self.camera.height - self.camera.safe_zone_size {
+            self.camera.move_y(self.padding + cursor_position.y - self.camera.computed_y() - self.camera.height + self.camera.safe_zone_size)
         }
     }
 
-    fn get_current_line(&mut self) -> &mut Line {
-        &mut self.lines[self.cursor.y as usize]
+    /// The size of the laid out document
+    fn get_content_size(&self) -> Vector2<f32> {
+        let last_line = self.lines.last().unwrap();
+        Vector2::new(self.content_width.unwrap_or_default(), (last_line.row_offset + last_line.row_count()) as f32 * self.font.borrow().char_height)
     }
 
-    fn get_current_buffer(&mut self) -> &mut Vec<String> {
-        &mut self.get_current_line().buffer
+    /// Move the camera by `delta` without moving the cursor, it can't be scrolled past the end of the document
+    pub fn scroll(&mut self, delta: Vector2<f32>) {
+        let (content_size, visible_size) = (self.get_content_size(), self.font.borrow().editor_size);
+        let scroll = self.camera.get_scroll();
+        let max_scroll = Vector2::new((content_size.x - visible_size.x).max(scroll.x), (content_size.y - visible_size.y).max(scroll.y));
+        self.camera.scroll_to(Vector2::new((scroll.x + delta.x).clamp(0., max_scroll.x), (scroll.y + delta.y).clamp(0., max_scroll.y)));
+        self.is_camera_free = true;
     }
 
-    pub fn get_selected_text(&mut self) -> String {
-        if !self.selection.is_valid() { return String::new() }
-        let mut buffer = vec![];
-        let lines_index = self.selection.get_lines_index(&self.lines);
-        let initial_y = self.selection.start().unwrap().y;
-        for (i, (start, end)) in lines_index.iter().enumerate() {
-            let y = initial_y as usize + i;
-            let mut text = String::new();
-            for j in *start .. *end {
-                let buffer_text = self.lines[y].buffer.get(j as usize);
-                if let Some(bt) = buffer_text { text.push_str(bt); }
+    /// Scroll by the distance of the mouse wheel or the trackpad, the content follows the wheel
+    pub fn scroll_by_wheel(&mut self, distance: MouseScrollDistance) {
+        let (char_width, char_height) = (self.font.borrow().char_width, self.font.borrow().char_height);
+        let delta = match distance {
+            MouseScrollDistance::Lines { x, y, .. } => Vector2::new(x as f32 * char_width, y as f32 * char_height) * WHEEL_SCROLL_LINES,
+            MouseScrollDistance::Pixels { x, y, .. } => Vector2::new(x as f32, y as f32),
+            MouseScrollDistance::Pages { x, y, .. } => {
+                let page_size = self.font.borrow().editor_size;
+                Vector2::new(x as f32 * page_size.x, y as f32 * page_size.y)
             }
-            text.push('\n');
-            buffer.push(text);
+        };
+        self.scroll(Vector2::new(-delta.x, -delta.y));
+    }
+
+    /// The scrollbars of the axes where the document doesn't fit in the editor
+    fn get_scrollbars(&self) -> Vec<Scrollbar> {
+        let margin = 2.;
+        let (content_size, visible_size) = (self.get_content_size(), self.font.borrow().editor_size);
+        let window_size = visible_size + self.offset + Vector2::new(self.padding, self.padding) * 2.;
+        let scroll = self.camera.position() - Vector2::new(self.camera.initial_x, self.camera.initial_y);
+        let vertical_track = (self.offset.y + margin, window_size.y - self.offset.y - SCROLLBAR_WIDTH - 3. * margin);
+        let horizontal_track = (self.offset.x + margin, window_size.x - self.offset.x - SCROLLBAR_WIDTH - 3. * margin);
+        [
+            Scrollbar::new(Orientation::Vertical, vertical_track, window_size.x - SCROLLBAR_WIDTH - margin, content_size.y, visible_size.y, scroll.y),
+            Scrollbar::new(Orientation::Horizontal, horizontal_track, window_size.y - SCROLLBAR_WIDTH - margin, content_size.x, visible_size.x, scroll.x),
+        ].into_iter().flatten().collect()
+    }
+
+    /// Grab the thumb of the scrollbar under `position`, a click elsewhere on the scrollbar scrolls by a page
+    /// Return `false` if there is no scrollbar under `position`
+    pub fn begin_scrollbar_drag(&mut self, position: Vector2<f32>) -> bool {
+        let Some(scrollbar) = self.get_scrollbars().into_iter().find(|scrollbar| scrollbar.contains(position)) else { return false; };
+        if let Some(grab_offset) = scrollbar.get_grab_offset(position) {
+            self.scrollbar_drag = Some((scrollbar.orientation, grab_offset));
+            return true;
         }
-        buffer.join("")
+        let page = self.font.borrow().editor_size * scrollbar.get_page_direction(position);
+        self.scroll(if scrollbar.orientation == Orientation::Vertical { Vector2::new(0., page.y) } else { Vector2::new(page.x, 0.) });
+        true
+    }
+
+    /// Scroll to follow the grabbed thumb, return `false` if no scrollbar is dragged
+    pub fn drag_scrollbar(&mut self, position: Vector2<f32>) -> bool {
+        let Some((orientation, grab_offset)) = self.scrollbar_drag else { return false; };
+        let Some(scrollbar) = self.get_scrollbars().into_iter().find(|scrollbar| scrollbar.orientation == orientation) else { return true; };
+        let (current, scroll) = (self.camera.get_scroll(), scrollbar.get_scroll(position, grab_offset));
+        self.camera.scroll_to(if orientation == Orientation::Vertical { Vector2::new(current.x, scroll) } else { Vector2::new(scroll, current.y) });
+        self.is_camera_free = true;
+        true
+    }
+
+    pub fn end_scrollbar_drag(&mut self) { self.scrollbar_drag = Option::None; }
+
+    /// Scroll while a selection is dragged out of the text area, faster as the mouse goes further, for `dt` ms
+    /// The selection, or the dragged text, follows the mouse up to the edge of the text area, return `false` if the mouse is inside it
+    pub fn auto_scroll_selection(&mut self, mouse_position: Vector2<f32>, dt: f32) -> bool {
+        let top_left = self.offset + Vector2::new(self.padding, self.padding);
+        let bottom_right = top_left + self.font.borrow().editor_size;
+        let distance_to = |position: f32, start: f32, end: f32| if position < start { position - start } else if position > end { position - end } else { 0. };
+        let distance = Vector2::new(
+            distance_to(mouse_position.x, top_left.x, bottom_right.x),
+            distance_to(mouse_position.y, top_left.y, bottom_right.y),
+        );
+        if distance == Vector2::ZERO { return false; }
+        let inside_position = Vector2::new(mouse_position.x.clamp(top_left.x, bottom_right.x), mouse_position.y.clamp(top_left.y, bottom_right.y));
+        let scroll = self.camera.get_scroll();
+        if !self.drag_text(inside_position) { self.update_selection(inside_position); }
+        self.camera.scroll_to(scroll); // the camera follows the mouse instead of the cursor
+        self.scroll(distance * (dt * AUTO_SCROLL_SPEED));
+        true
     }
 
-    pub fn new_line(&mut self) {
-        self.delete_selection();
-        let mut new_line = Line::new(Rc::clone(&self.font));
-        let index = self.cursor.y as usize + 1;
-        let x = self.cursor.x as usize;
-        let current_buffer = self.get_current_buffer();
-        let text_after_cursor = current_buffer.drain(x .. current_buffer.len());
-        new_line.add_text(&text_after_cursor.as_slice().join(""));
-        drop(text_after_cursor);
-        self.lines.insert(index, new_line);
-        // Pattern matching for new line
-        if index == 1 {
-            self.move_cursor(Vector2::new(0, self.cursor.y + 1));
-            return;
-        }
-        let line_before_buffer= self.lines.get(index - 1).unwrap().buffer.clone();
-        let line_before_alignement = self.lines.get(index - 1).unwrap().alignment.clone();
-        let last_line = self.lines.get_mut(index).unwrap();
-        last_line.set_alignment(line_before_alignement); // Preserve the alignement
-        let text = line_before_buffer.join("");
-        let nb_whitespace = text.len() - text.trim_start().len();
-        if text.trim_start().starts_with('-') &&  text.trim().len() > 1 {
-            let new_text = " ".repeat(nb_whitespace) + "- "; // TODO: Aadapt the number of spaces after the dash
-            last_line.add_text(&new_text);
-            self.move_cursor(Vector2::new(nb_whitespace as u32 + 2, self.cursor.y + 1));
-            self.menu.open_with(vec![MenuItem::new("Annuler", MenuAction::CancelChip) ]);
-        } else {
-            self.move_cursor(Vector2::new(0, self.cursor.y + 1));
+    /// The first and last lines shown through the camera, even partly
+    fn get_visible_lines(&self) -> (u32, u32) {
+        let char_height = self.font.borrow().char_height;
+        let (top, bottom) = self.camera.get_visible_y_range();
+        let (first_row, last_row) = ((top / char_height).max(0.) as u32, (bottom / char_height).max(0.) as u32);
+        let last_line = self.lines.len() - 1;
+        let first = cmp::min(self.lines.partition_point(|line| line.row_offset + line.row_count() <= first_row), last_line);
+        let last = self.lines.partition_point(|line| line.row_offset <= last_row).saturating_sub(1);
+        (first as u32, cmp::max(first, last) as u32)
+    }
+
+    /// The buffer position of the text displayed at `position` in the document
+    fn get_position_at(&self, position: Vector2<f32>) -> Position { get_position_at(&self.lines, position) }
+
+    /// The column at which the lines are soft wrapped, if they are
+    fn get_wrap_column(&self) -> Option<u32> {
+        let window_column = (self.font.borrow().editor_size.x / self.font.borrow().char_width) as u32;
+        match self.wrap_mode {
+            WrapMode::None => Option::None,
+            WrapMode::Window => Some(window_column),
+            WrapMode::Column(column) => Some(cmp::min(column, window_column)),
         }
     }
 
-    fn duplicate_line(&mut self) {
-        let cursor_pos = Vector2::new(self.cursor.x, self.cursor.y);
-        let index_start = self.selection.start().unwrap_or(cursor_pos).y as usize;
-        let index_end = self.selection.end().unwrap_or(cursor_pos).y as usize;
-        let line_slice = self.lines[index_start..=index_end].to_vec();
-        for (i, line) in line_slice.iter().enumerate() {
-            self.lines.insert(index_start + i, (*line).clone());
+    /// Keep the first row of each line after the rows of the lines above it
+    fn update_row_offsets(&mut self) {
+        let mut row_offset = 0;
+        for line in &mut self.lines {
+            line.row_offset = row_offset;
+            row_offset += line.row_count();
         }
-        self.move_cursor(Vector2::new(self.cursor.x, self.cursor.y + line_slice.len() as u32))
     }
 
-    fn switch_lines(&mut self, dir: i32) {
-        let cursor_pos = Vector2::new(self.cursor.x, self.cursor.y);
-        let index_start = self.selection.start().unwrap_or(cursor_pos).y as usize;
-        let index_end = self.selection.end().unwrap_or(cursor_pos).y as usize;
-        if dir < 0 {
-            for i in index_start..=index_end { self.lines.swap(i, (i as i32 - 1).abs() as usize); }
-        } else if dir > 0 {
-            for i in 0..=(index_end - index_start) { self.lines.swap(index_end - i, index_end - i as usize + 1); }
-        }
-        if self.selection.is_valid() {
-            self.selection.set_start(Vector2::new(self.selection.start().unwrap().x, (self.selection.start().unwrap().y as i32 + dir) as u32));
-            self.selection.set_end(Vector2::new(self.selection.end().unwrap().x, (self.selection.end().unwrap().y as i32 + dir) as u32));
-        }
-        self.move_cursor(Vector2::new(self.cursor.x, (self.cursor.y as i32 + dir) as u32))
-    }
+    pub fn get_selected_text(&self) -> String { self.document.get_selected_text() }
 
-    pub fn add_text(&mut self, text: &str) {
-        for c in text.chars() {
-            self.add_char(c.to_string());
-        }
+    /// Copy the selections of every cursor and remove them, as a single undo unit
+    pub fn cut(&mut self) {
+        if let Some(text) = self.document.cut() { self.set_clipboard_contents(text); }
+        self.sync_document();
     }
 
-    pub fn cancel_chip(&mut self) {
-        self.get_current_line().empty();
-        self.cursor.move_to(0, self.cursor.y);
-        self.new_line(); // Move the cursor to the new created line
-        self.update_text_layout();
+    pub fn add_text(&mut self, text: &str) {
+        self.edit(|document| document.add_text(text));
     }
 
     pub fn toggle_contextual_menu(&mut self) {
         let mut items = vec![];
-        if self.selection.is_valid() {
+        if self.document.selection.is_valid() {
             for i in [
                 MenuItem::new("Copy", MenuAction::Copy),
                 MenuItem::new("Cut", MenuAction::Cut),
@@ -552,7 +585,7 @@ impl Editor {
     }
 
     pub fn toggle_ai_contextual_menu(&mut self) {
-        if !self.selection.is_valid() { self.select_current_word(); }
+        if !self.document.selection.is_valid() { self.select_current_word(); }
         self.menu.open_with(vec![
             MenuItem::new("AI Correct", MenuAction::AICorrect),
             MenuItem::new("AI Action >", MenuAction::AIQuestionWithInput),
@@ -601,7 +634,7 @@ impl Editor {
     }
 
     fn _contextual_submenu_test(&mut self) {
-        let sub_menu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone().unwrap(), vec![
+        let sub_menu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone(), vec![
             MenuItem::new("SubMenu 1", MenuAction::Void),
             MenuItem::new("SubMenu Input", MenuAction::PrintWithInput),
             MenuItem::new("SubMenu 3", MenuAction::Void),
@@ -636,61 +669,12 @@ impl Editor {
         valid_file_path.into_os_string().to_str().unwrap().to_string()
     }
 
-    /// Add a range to a buffer according to the underline/bold rules
-    fn add_range_to_buffer<T: RangeTrait>(range_like: T, buffer: &mut Vec<T>) {
-        // switch on the type of the generic parameter to determine wether it's a simple Range or a StyledRange
-        let range = range_like.get_range();
-        if !range.is_valid() { return; }
-        let len = buffer.len();
-        for mut i in 0 .. len {
-            assert!(len >= 1);
-            i = len - 1 - i;
-            let buffer_range = buffer.get_mut(i).unwrap().get_range();
-            if range == buffer_range { buffer.remove(i); return; }
-            else if range.include(buffer_range) { buffer.remove(i); }
-            else if buffer_range.include(range) {
-                assert!(buffer_range.is_valid());
-                let before = T::new(buffer_range.get_real_start().unwrap(), range.get_real_start().unwrap());
-                let after = T::new(range.get_real_end().unwrap(), buffer_range.get_real_end().unwrap());
-                if before.is_valid() { buffer.push(before);  }
-                if after.is_valid() { buffer.push(after);  }
-                buffer.remove(i);
-                return;
-            }
-        }
-        buffer.push(range_like); // to push the proper struct and not only the range
-    }
-
     pub fn underline(&mut self) {
-        Self::add_range_to_buffer(StyleRange::new_underline(self.selection.get_range()), &mut self.style_buffer);
-        self.set_dirty(true);
+        self.edit(Document::underline);
     }
 
     pub fn bold(&mut self) {
-        Self::add_range_to_buffer(StyleRange::new_bold(self.selection.get_range()), &mut self.style_buffer);
-        self.font.borrow_mut().style_changed = true;
-        self.set_dirty(true);
-    }
-
-    pub fn colorize(&mut self, color: Color) {
-        Self::add_range_to_buffer(StyleRange::new_colored(self.selection.get_range(), color), &mut self.style_buffer);
-        self.font.borrow_mut().style_changed = true;
-        self.set_dirty(true);
-    }
-
-    pub fn set_line_alignment(&mut self, alignment: TextAlignment) {
-        if self.selection.is_valid() {
-            let start = self.selection.start().unwrap().y as usize;
-            let end = self.selection.end().unwrap().y as usize;
-            for (i, line) in self.lines.iter_mut().enumerate() {
-                if start <= i && i <= end {
-                    line.set_alignment(alignment.clone());
-                }
-            }
-        } else {
-            self.get_current_line().set_alignment(alignment);
-        }
-        self.set_dirty(true);
+        self.edit(Document::bold);
     }
 
     fn increase_font_size(&mut self) {
@@ -709,41 +693,270 @@ impl Editor {
         self.send_event(EditorEvent::Redraw);
     }
 
-    fn find_next(&mut self) {
-        self.menu.open_with(vec![MenuItem::new("Find:", MenuAction::FindAndJumpWithInput)])
+    /// Open the find/replace panel, a search in the selection is limited to the current one
+    fn toggle_search_menu(&mut self) {
+        if self.menu.is_visible { return self.menu.close(); }
+        self.document.set_search_scope();
+        let mut items = vec![
+            MenuItem::new("Find:", MenuAction::SearchWithInput),
+            MenuItem::new("Replace with:", MenuAction::ReplacementWithInput),
+            MenuItem::separator(),
+            MenuItem::new("Next match", MenuAction::FindNext),
+            MenuItem::new("Previous match", MenuAction::FindPrevious),
+            MenuItem::new("Replace", MenuAction::ReplaceMatch),
+            MenuItem::new("Replace all", MenuAction::ReplaceAllMatches),
+            MenuItem::separator(),
+        ];
+        items.extend(SearchOption::ALL.iter().map(|&option| MenuItem::new(&self.document.search.get_option_title(option), MenuAction::ToggleSearchOption(option))));
+        self.menu.open_with(items);
+        // The fields keep the previous search
+        let (query, replacement) = (self.document.search.query.clone(), self.document.search.replacement.clone());
+        if let Some(input) = &mut self.menu.items[0].input { input.set_placeholder(&query); }
+        if let Some(input) = &mut self.menu.items[1].input { input.set_placeholder(&replacement); }
+        self.update_search_counter();
+    }
+
+    fn is_search_menu_open(&self) -> bool {
+        self.menu.is_visible && self.menu.items.first().is_some_and(|item| item.action == MenuAction::SearchWithInput)
+    }
+
+    /// Show in the find chip which match is selected, ex: `3 of 17`
+    fn update_search_counter(&mut self) {
+        if !self.is_search_menu_open() { return; }
+        let document = &mut self.document;
+        let selection = document.selection.start().zip(document.selection.end()).map(|(start, end)| (document.buffer.get_byte_index(start), document.buffer.get_byte_index(end)));
+        let is_empty = document.search.query.is_empty();
+        let matches = document.get_search_matches();
+        let title = match matches.iter().position(|found| Some(*found) == selection) {
+            _ if is_empty => "Find:".to_string(),
+            _ if matches.is_empty() => "Find: no match".to_string(),
+            Some(index) => format!("Find: {} of {}", index + 1, matches.len()),
+            None => format!("Find: {} matches", matches.len()),
+        };
+        self.menu.items[0].title = title;
+        self.menu.update_content();
+    }
+
+    /// Highlight the visible matches of the search while the find/replace panel is open
+    fn render_search_matches(&mut self, visible_lines: (u32, u32), graphics: &mut Graphics2D) {
+        if !self.is_search_menu_open() { return; }
+        let (first_line, last_line) = visible_lines;
+        let buffer = &self.document.buffer;
+        let visible_start = buffer.get_byte_index(Position::new(0, first_line));
+        let visible_end = buffer.get_byte_index(Position::new(buffer.line_len(last_line), last_line));
+        let matches = self.document.get_search_matches();
+        let first_match = matches.partition_point(|(_, end)| *end < visible_start);
+        let visible_matches: Vec<(usize, usize)> = matches[first_match ..].iter().take_while(|(start, _)| *start <= visible_end).copied().collect();
+        for (start, end) in visible_matches {
+            let (start, end) = (self.document.buffer.get_position_at_byte(start), self.document.buffer.get_position_at_byte(end));
+            self.render_text_background(start, end, Color::from_int_rgba(255, 236, 153, 255), graphics);
+        }
     }
 
-    pub fn find(&mut self, text: &str) {
-        let cursor_y = self.cursor.y as usize;
-        for i in 0 .. self.lines.len() {
-            let line_index = (i + cursor_y) % self.lines.len(); // begin the search at cursor.y then loop
-            let start = if line_index as u32 == self.cursor.y { self.cursor.x as usize } else { 0 };
-            let match_index = &self.lines[line_index].get_text()[start..].find(text); //.map(|i| i);
-            if let Some(index) = match_index {
-                self.selection.reset();
-                self.move_cursor(Vector2::new((start + index) as u32, line_index as u32));
-                break;
+    /// Fill the background of the text between two positions, on every row it spans
+    fn render_text_background(&self, start: Position, end: Position, color: Color, graphics: &mut Graphics2D) {
+        let (char_width, char_height) = (self.font.borrow().char_width, self.font.borrow().char_height);
+        for y in start.y ..= end.y {
+            let left = if y == start.y { start.x } else { 0 };
+            let right = if y == end.y { end.x } else { self.document.buffer.line_len(y) };
+            for (position, right) in self.lines[y as usize].get_visual_spans(left as f32 * char_width, right as f32 * char_width) {
+                let top_left = Vector2::new(position.x - self.camera.computed_x(), position.y - self.camera.computed_y());
+                graphics.draw_rectangle(Rectangle::new(top_left, Vector2::new(right - self.camera.computed_x(), top_left.y + char_height)), color);
             }
         }
     }
 
+    /// The bracket or quote next to the cursor and the one matching it, found again once the cursor moved or the text changed
+    fn get_bracket_pair(&mut self) -> Option<(Position, Position)> {
+        let cursor = self.document.cursor;
+        if self.bracket_pair.is_none_or(|(bracket_cursor, _)| bracket_cursor != cursor) {
+            self.bracket_pair = Some((cursor, find_bracket_pair(&self.document.buffer, cursor)));
+        }
+        self.bracket_pair.unwrap().1
+    }
+
+    /// Highlight the bracket or quote next to the cursor and the one matching it
+    fn render_bracket_pair(&mut self, visible_lines: (u32, u32), graphics: &mut Graphics2D) {
+        if self.document.selection.is_valid() { return; }
+        let Some((bracket, other)) = self.get_bracket_pair() else { return; };
+        for position in [bracket, other] {
+            if !(visible_lines.0 ..= visible_lines.1).contains(&position.y) { continue; }
+            self.render_text_background(position, Position::new(position.x + 1, position.y), Color::from_int_rgba(205, 222, 250, 255), graphics);
+        }
+    }
+
+    /// Place the cursor at `position` after a click, or select the word under it after a double click and its line after a triple click
+    pub fn select_at_click(&mut self, position: Position, click_count: u32) {
+        self.drag_unit = match click_count { 1 => SelectionUnit::Char, 2 => SelectionUnit::Word, _ => SelectionUnit::Line };
+        if self.drag_unit != SelectionUnit::Char { self.drag_anchor = self.get_unit_bounds(position, self.drag_unit); }
+        let (is_click, (anchor_start, anchor_end)) = (self.drag_unit == SelectionUnit::Char, self.drag_anchor);
+        self.edit(|document| {
+            document.remove_secondary_cursors();
+            document.selection.reset();
+            if is_click {
+                document.move_cursor(position);
+                return document.begin_selection();
+            }
+            document.selection.set(anchor_start, anchor_end);
+            document.move_cursor(anchor_end);
+        });
+    }
+
+    /// Start dragging the selected text when `position` is on it, return `false` otherwise
+    pub fn begin_text_drag(&mut self, position: Vector2<f32>) -> bool {
+        let selection = &self.document.selection;
+        if !selection.is_valid() || selection.is_block() || !self.document.secondary_cursors.is_empty() { return false; }
+        let (start, end) = (selection.start().unwrap(), selection.end().unwrap());
+        let index = self.get_mouse_position_index(position);
+        if !is_before(start, index) || !is_before(index, end) { return false; } // a click on a bound of the selection moves the cursor
+        self.drop_position = Some(index);
+        true
+    }
+
+    /// Move the drop position under the mouse, return `false` if no text is dragged
+    pub fn drag_text(&mut self, position: Vector2<f32>) -> bool {
+        if self.drop_position.is_none() { return false; }
+        self.drop_position = Some(self.get_mouse_position_index(position));
+        true
+    }
+
+    /// Move the dragged text where it is dropped, or copy it there while `alt` is held
+    /// Dropping it back on the selection places the cursor as a click would
+    pub fn drop_text(&mut self) {
+        let Some(position) = self.drop_position.take() else { return; };
+        if self.document.is_on_selection(position) { return self.select_at_click(position, 1); }
+        let is_copy = self.modifiers.alt();
+        self.edit(|document| document.move_selected_text(position, is_copy));
+    }
+
+    /// A caret where the dragged text would be dropped
+    fn render_drop_position(&self, graphics: &mut Graphics2D) {
+        let Some(position) = self.drop_position else { return; };
+        if self.document.is_on_selection(position) { return; }
+        let display_position = self.lines[position.y as usize].to_visual_position(position.x as f32 * self.font.borrow().char_width);
+        draw_rounded_line(
+            display_position.x - self.camera.computed_x() + CURSOR_OFFSET_X,
+            display_position.y - self.camera.computed_y(),
+            CURSOR_WIDTH,
+            self.font.borrow().char_height,
+            Color::GRAY,
+            graphics
+        );
+    }
+
+    /// The drag that follows a double or triple click is over, the next selections extend by chars
+    pub fn end_selection_drag(&mut self) { self.drag_unit = SelectionUnit::Char; }
+
+    /// The bounds of the word or the line at `position`
+    fn get_unit_bounds(&self, position: Position, unit: SelectionUnit) -> (Position, Position) {
+        let buffer = &self.document.buffer;
+        match unit {
+            SelectionUnit::Char => (position, position),
+            SelectionUnit::Word => {
+                let (start, end) = buffer.get_word_at(position.x, position.y);
+                (Position::new(start, position.y), Position::new(end, position.y))
+            },
+            SelectionUnit::Line => (Position::new(0, position.y), Position::new(buffer.line_len(position.y), position.y)),
+        }
+    }
+
+    /// Select from the word or the line that started the drag to the one at `position`, the cursor follows the mouse
+    fn extend_selection_by_unit(&mut self, position: Position) {
+        let (start, end) = self.get_unit_bounds(position, self.drag_unit);
+        let (anchor_start, anchor_end) = self.drag_anchor;
+        self.edit(|document| if is_before(start, anchor_start) {
+            document.selection.set(start, anchor_end);
+            document.move_cursor(start);
+        } else {
+            let end = vector_max(end, anchor_end);
+            document.selection.set(anchor_start, end);
+            document.move_cursor(end);
+        });
+    }
+
+    /// Search `query` while it is typed, from the start of the current match so it stays selected while it matches
+    pub fn search_for(&mut self, query: &str) {
+        self.edit(|document| document.search_for(query));
+        self.update_search_counter();
+    }
+
+    pub fn find_next_match(&mut self) {
+        self.edit(Document::find_next_match);
+        self.update_search_counter();
+    }
+
+    pub fn find_previous_match(&mut self) {
+        self.edit(Document::find_previous_match);
+        self.update_search_counter();
+    }
+
+    pub fn toggle_search_option(&mut self, option: SearchOption) {
+        self.edit(|document| document.toggle_search_option(option));
+        let title = self.document.search.get_option_title(option);
+        if let Some(item) = self.menu.items.iter_mut().find(|item| item.action == MenuAction::ToggleSearchOption(option)) { item.title = title; }
+        self.menu.update_content();
+        self.update_search_counter();
+    }
+
+    /// Replace the selected match, if it is one, and select the next match
+    pub fn replace_match(&mut self) {
+        self.edit(Document::replace_match);
+        self.update_text_layout();
+        self.update_search_counter();
+    }
+
+    /// Replace all the matches as a single undo step, the style ranges follow the replaced text
+    pub fn replace_all_matches(&mut self) {
+        self.edit(Document::replace_all_matches);
+        self.update_text_layout();
+        self.update_search_counter();
+    }
+
     fn get_stats(&self) -> Vec<String> {
         // TODO: get stats of selection if there is one instead of the whole file
-        let words_count = self.lines.iter().fold(0, |acc, line| acc + line.get_word_count());
-        let char_count = self.lines.iter().fold(0, |acc, line| acc + line.buffer.len());
+        let buffer = &self.document.buffer;
+        let words_count = buffer.get_word_count();
+        let char_count: u32 = (0 .. buffer.line_count()).map(|y| buffer.line_len(y)).sum(); // line breaks are not counted
         let update_duration = self.stats.update_duration.as_micros() as f64 / 1000.;
+        let layout_duration = self.stats.layout_duration.as_micros() as f64 / 1000.;
         let draw_duration = self.stats.draw_duration.as_micros() as f64 / 1000.;
         vec![
             iformat!("Nombre de mots: {words_count}"),
             iformat!("Nombre de caractères: {char_count}"),
             iformat!("Nombre de lignes: {self.lines.len()}"),
-            iformat!("Position du curseur: ({self.cursor.x}, {self.cursor.y})"),
+            iformat!("Position du curseur: ({self.document.cursor.x}, {self.document.cursor.y})"),
             iformat!("---"),
             iformat!("Update time: {update_duration:.1}ms"),
+            iformat!("Layout time: {layout_duration:.1}ms"),
             iformat!("Draw time: {draw_duration:.1}ms"),
         ]
     }
 
+    fn toggle_go_to_popup(&mut self) {
+        if self.menu.is_visible { return self.menu.close(); }
+        self.menu.open_with(vec![MenuItem::new(&format!("Go to line (1 - {}):", self.document.buffer.line_count()), MenuAction::GoToWithInput)]);
+        let position = format!("{}:{}", self.document.cursor.y + 1, self.document.cursor.x + 1);
+        if let Some(input) = &mut self.menu.items[0].input { input.set_placeholder(&position); }
+    }
+
+    /// Move the cursor to `target`, ex: `120`, `120:5` or `-3`, and show it in the middle of the camera
+    pub fn go_to(&mut self, target: &str) {
+        if let Some(target) = GoTo::parse(target) { self.go_to_target(target); }
+    }
+
+    pub fn go_to_target(&mut self, target: GoTo) {
+        self.edit(|document| document.go_to_target(target));
+        self.center_camera_on_cursor();
+    }
+
+    fn center_camera_on_cursor(&mut self) {
+        let char_height = self.font.borrow().char_height;
+        let line = &self.lines[self.cursor.y as usize];
+        let cursor_y = line.to_visual_position(self.cursor.real_x()).y + char_height / 2.;
+        let (top, bottom) = self.camera.get_visible_y_range();
+        self.camera.move_y(cursor_y - (top + bottom) / 2.);
+    }
+
     fn toggle_stats_popup(&mut self) {
         if self.menu.is_visible { return self.menu.close(); }
         self.menu.open_with(self.get_stats().iter().map(|s| {
@@ -753,16 +966,15 @@ impl Editor {
         self.send_event(EditorEvent::Focus(FocusElement::Editor));
     }
 
+    /// `Null` when the key is missing, ex: in the prefs of an older version, so the default value is used
     fn get_prefs_key(&mut self, key: &str) -> serde_yaml::Value {
-        if let Some(prefs) = &self.cached_prefs {
-            prefs.get(key).unwrap().to_owned()
-        } else {
+        if self.cached_prefs.is_none() {
             let prefs_path = Self::get_file_path("./resources/prefs.yaml");
             let prefs_str = fs::read_to_string(prefs_path).expect("Can't find the preference file");
             let prefs: serde_yaml::Value = serde_yaml::from_str(&prefs_str).expect("Invalid preferences");
-            self.cached_prefs = Some(prefs.clone());
-            prefs.get(key).unwrap().to_owned()
+            self.cached_prefs = Some(prefs);
         }
+        self.cached_prefs.as_ref().and_then(|prefs| prefs.get(key)).cloned().unwrap_or(serde_yaml::Value::Null)
     }
 
     fn set_prefs_key(&mut self, key: &str, value: serde_yaml::Value) {
@@ -772,13 +984,66 @@ impl Editor {
             let prefs: serde_yaml::Value = serde_yaml::from_str(&prefs_str).expect("Invalid preferences");
             prefs
         };
-        *prefs.get_mut(key).unwrap() = value.into();
+        prefs[key] = value; // the key is added if it is missing
         let mut buffer = Vec::new();
         serde_yaml::to_writer(&mut buffer, &prefs).unwrap();
         fs::write(Self::get_file_path("./resources/prefs.yaml"), buffer).expect("Unable to write to the preference file");
         self.cached_prefs = Option::None;
     }
 
+    fn get_file_extension(&self) -> Option<String> {
+        let filepath = self.filepath.as_ref()?;
+        Some(Path::new(filepath).extension()?.to_str()?.to_string())
+    }
+
+    /// Read in the prefs if the typographic substitutions are displayed for the type of the current file
+    fn update_substitutions_from_prefs(&mut self) {
+        let Some(extension) = self.get_file_extension() else { return; };
+        let substitutions_prefs = self.get_prefs_key("typographic_substitutions");
+        self.use_substitutions = substitutions_prefs.get(&extension).and_then(|value| value.as_bool()).unwrap_or(DEFAULT_USE_SUBSTITUTIONS);
+    }
+
+    /// Read in the prefs the pairs of chars closed automatically for the type of the current file, ex: `["()", "\"\""]`
+    fn update_auto_pairs_from_prefs(&mut self) {
+        let pairs_prefs = self.get_prefs_key("auto_pairs");
+        let extension = self.get_file_extension().unwrap_or_default();
+        let pairs: Option<Vec<&str>> = pairs_prefs.get(&extension).or(pairs_prefs.get("default"))
+            .and_then(|value| value.as_sequence())
+            .map(|pairs| pairs.iter().filter_map(|pair| pair.as_str()).collect());
+        self.document.auto_pairs = parse_auto_pairs(&pairs.unwrap_or(DEFAULT_AUTO_PAIRS.to_vec()));
+    }
+
+    /// Read in the prefs how the lines wider than the editor are displayed: `true` wraps at the window width,
+    /// a number at this column and `false` disables the soft wrap
+    pub fn update_wrap_mode_from_prefs(&mut self) {
+        self.wrap_mode = match self.get_prefs_key("soft_wrap") {
+            serde_yaml::Value::Bool(false) => WrapMode::None,
+            serde_yaml::Value::Number(column) => WrapMode::Column(column.as_u64().unwrap_or(80) as u32),
+            _ => WrapMode::Window,
+        };
+    }
+
+    /// Use the indentation of `text` if it has one, else the one of the prefs
+    fn update_indentation(&mut self, text: &str) {
+        let prefs = self.get_prefs_key("indentation");
+        let default = Indentation {
+            use_tabs: prefs.get("use_tabs").and_then(|value| value.as_bool()).unwrap_or(false),
+            width: prefs.get("width").and_then(|value| value.as_u64()).unwrap_or(4) as u32,
+        };
+        self.document.indentation = Indentation::detect(text, default);
+    }
+
+    /// Show or hide the typographic substitutions and remember it for the type of the current file
+    fn toggle_substitutions(&mut self) {
+        self.use_substitutions = !self.use_substitutions;
+        if let Some(extension) = self.get_file_extension() {
+            let mut substitutions_prefs = self.get_prefs_key("typographic_substitutions");
+            substitutions_prefs[extension.as_str()] = serde_yaml::Value::Bool(self.use_substitutions);
+            self.set_prefs_key("typographic_substitutions", substitutions_prefs);
+        }
+        self.update_text_layout();
+    }
+
     fn get_recent_files(&mut self) -> Vec<(String, String)> {
         lazy_static! { static ref NAME_REGEX: Regex = Regex::new(r#"([\w\s_-]+).(\w+)$"#).unwrap(); }
         let files_yaml = self.get_prefs_key("recent_files");
@@ -836,7 +1101,7 @@ impl Editor {
         for (name, path) in self.get_recent_paths() {
             path_items.push(MenuItem::new(&name, MenuAction::NewFileWithInput(path)));
         }
-        let new_file_submenu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone().unwrap(), path_items);
+        let new_file_submenu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone(), path_items);
         let items = vec![
             MenuItem::new("New Empty File", MenuAction::NewFile("new-file.txt".into())),
             MenuItem::new_with_submenu("New File ...", new_file_submenu),
@@ -858,10 +1123,7 @@ impl Editor {
             .unwrap()
             .to_str()
             .unwrap();
-        self.lines
-            .get_mut(0)
-            .unwrap()
-            .add_text(text);
+        self.edit(|document| { document.insert_text(Position::ZERO, text); });
     }
 
     /// Ask for the filepath if there is no one specified else save to the current one
@@ -879,7 +1141,7 @@ impl Editor {
         for (name, path) in self.get_recent_paths() {
             path_items.push(MenuItem::new(&name, MenuAction::SaveWithInput(path)));
         }
-        let path_submenu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone().unwrap(), path_items);
+        let path_submenu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone(), path_items);
         let mut file_items = vec![MenuItem::new_with_submenu("Save to >", path_submenu), MenuItem::separator()];
         for (name, path) in self.get_recent_files() {
             file_items.push(MenuItem::new(&name, MenuAction::Save(path)));
@@ -896,19 +1158,16 @@ impl Editor {
 
     /// Save to a specific file
     pub fn save_to_file(&mut self, filepath: &str) {
-        if filepath.ends_with(".txt") { self.save_to_txt_file(filepath) }
-        else if filepath.ends_with(".drn") { self.save_to_drn_file(filepath) }
+        if filepath.ends_with(".drn") { self.save_to_drn_file(filepath) }
+        else { self.save_to_txt_file(filepath) }
+        self.document.history.mark_saved();
         self.set_dirty(false);
     }
 
     pub fn save_to_txt_file(&mut self, filepath: &str) {
         let valid_filepath = self.get_valid_path_or_create_it(filepath);
         self.filepath = Some(filepath.into());
-        let mut data = String::new();
-        for (i, line) in (&self.lines).iter().enumerate() {
-            data.push_str(&line.buffer.clone().join(""));
-            if i + 1 != self.lines.len() { data.push('\n') }
-        }
+        let data = self.document.file_format.encode(&self.document.buffer.to_string());
         fs::write(valid_filepath, &data).expect(&format!("Unable to write file to {}", filepath));
         self.send_event(EditorEvent::LoadFile(filepath.into()))
     }
@@ -919,8 +1178,9 @@ impl Editor {
         let mut encode = String::new();
         // Encode underline
         encode.push_str("#u: ");
-        let bold_buffer: Vec<Range> = self.style_buffer.iter().filter(|sr| sr.bold == true).map(|sr| sr.range).collect();
-        let underline_buffer: Vec<Range> = self.style_buffer.iter().filter(|sr| sr.underline == true).map(|sr| sr.range).collect();
+        // The ranges are stored in chars to stay independent of the unicode version used to split graphemes
+        let bold_buffer: Vec<Range> = self.document.style_buffer.iter().filter(|sr| sr.bold == true).map(|sr| self.to_char_range(sr.range)).collect();
+        let underline_buffer: Vec<Range> = self.document.style_buffer.iter().filter(|sr| sr.underline == true).map(|sr| self.to_char_range(sr.range)).collect();
 
         let underline_ranges = underline_buffer
             .iter()
@@ -938,21 +1198,29 @@ impl Editor {
             .collect::<String>();
         encode.push_str(&bold_ranges);
         encode.push_str("\n");
-        for (i, line) in (&self.lines).iter().enumerate() {
-            encode.push_str(&line.buffer.clone().join(""));
-            if i + 1 != self.lines.len() { encode.push('\n') }
-        }
-        fs::write(valid_filepath, &encode).expect(&format!("Unable to write file to {}", filepath));
+        encode.push_str(&self.document.buffer.to_string());
+        fs::write(valid_filepath, self.document.file_format.encode(&encode)).expect(&format!("Unable to write file to {}", filepath));
         self.send_event(EditorEvent::LoadFile(filepath.into()))
     }
 
+    fn to_char_range(&self, range: Range) -> Range {
+        if !range.is_valid() { return range; }
+        let buffer = &self.document.buffer;
+        Range::new(buffer.to_char_position(range.start.unwrap()), buffer.to_char_position(range.end.unwrap()))
+    }
+
+    fn to_grapheme_range(&self, range: Range) -> Range {
+        let buffer = &self.document.buffer;
+        Range::new(buffer.to_grapheme_position(range.start.unwrap()), buffer.to_grapheme_position(range.end.unwrap()))
+    }
+
     /// Ask for the filepath to load
     pub fn load(&mut self) {
         let mut path_items = vec![];
         for (name, path) in self.get_recent_paths() {
             path_items.push(MenuItem::new(&name, MenuAction::OpenWithInput(path)));
         }
-        let path_submenu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone().unwrap(), path_items);
+        let path_submenu = ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone(), path_items);
         let mut menu_items = vec![
             MenuItem::new_with_submenu("Open ...", path_submenu),
             MenuItem::separator()
@@ -963,32 +1231,42 @@ impl Editor {
         self.menu.open_with(menu_items);
     }
 
-    /// Load a specific path
+    /// Open a file chosen in a menu or dropped onto the window, a missing file is created
+    /// The unsaved changes are only discarded once confirmed
+    pub fn open_file(&mut self, filepath: &str) {
+        let path = Path::new(filepath);
+        if path.is_dir() { return; }
+        if self.document.is_dirty() {
+            let name = get_file_name(filepath);
+            return self.menu.open_with(vec![
+                MenuItem::new(&iformat!("Discard the changes and open {name}"), MenuAction::DiscardAndOpen(filepath.to_string())),
+                MenuItem::new("Cancel", MenuAction::CloseMenu),
+            ]);
+        }
+        if path.exists() { self.load_file(filepath) } else { self.new_file(filepath) }
+    }
+
+    /// Open `filepath` once the user confirmed that the unsaved changes can be lost
+    pub fn discard_changes_and_open(&mut self, filepath: &str) {
+        self.document.set_dirty(false);
+        self.open_file(filepath);
+    }
+
+    /// Load a specific path, the files other than `.drn` are loaded as plain text
     pub fn load_file(&mut self, filepath: &str) {
-        if filepath.ends_with(".txt") { self.load_txt_file(filepath) }
-        else if filepath.ends_with(".drn") { self.load_drn_file(filepath) }
+        if filepath.ends_with(".drn") { self.load_drn_file(filepath) }
+        else { self.load_txt_file(filepath) }
         // TODO: .rtf ?
     }
 
     pub fn load_txt_file(&mut self, filepath: &str) {
         let valid_filepath = fs::canonicalize(filepath).expect("Invalid filepath");
-        self.lines = vec![Line::new(Rc::clone(&self.font))];
-        self.style_buffer = vec![];
-        self.selection.reset();
+        let Some(file_content) = self.read_file(&valid_filepath, filepath) else { return; };
         self.filepath = Some(filepath.into());
-        let file_content = fs::read_to_string(&valid_filepath).expect(&format!("Unable to load file to {}", filepath));
-        for (i, line) in file_content.split('\n').enumerate() {
-            if i < self.lines.len() {
-                self.lines.push(Line::new(Rc::clone(&self.font)));
-            }
-            self.lines[i].add_text(line);
-        }
-        let mut i : usize = self.lines.len() - 1;
-        while i > 0 && self.lines[i].is_empty() { // Remove the empty lines at the end of the file
-            self.lines.pop();
-            i -= 1;
-        }
-        self.cursor.move_to(0, 0);
+        self.edit(|document| document.load_text(&file_content));
+        self.update_substitutions_from_prefs();
+        self.update_auto_pairs_from_prefs();
+        self.update_indentation(&file_content);
         self.update_text_layout();
         if filepath != "new-file.txt" {
             self.add_to_recent_paths(filepath);
@@ -999,30 +1277,20 @@ impl Editor {
 
     pub fn load_drn_file(&mut self, filepath: &str) {
         let valid_filepath = fs::canonicalize(filepath).expect("Invalid filepath");
-        self.lines = vec![Line::new(Rc::clone(&self.font))];
-        self.selection.reset();
+        let Some(file_content) = self.read_file(&valid_filepath, filepath) else { return; };
         self.filepath = Some(filepath.into());
-        self.style_buffer = vec![];
-        let file_content = fs::read_to_string(&valid_filepath).expect(&format!("Unable to load file to {}", filepath));
-        let content_lines = file_content.split('\n').collect();
+        let content_lines: Vec<&str> = file_content.split('\n').collect();
+        // Handle text
+        let text = content_lines[2..].join("\n");
+        self.edit(|document| document.load_text(&text));
         // Handle style
         let underline_buffer = Range::get_ranges_from_drn_line("#u:", &content_lines);
         let bold_buffer = Range::get_ranges_from_drn_line("#b:", &content_lines);
-        for range in underline_buffer { self.style_buffer.push(StyleRange::new_underline(range)) }
-        for range in bold_buffer { self.style_buffer.push(StyleRange::new_bold(range)) }
-        // Handle text
-        for (i, line) in content_lines[2..].iter().enumerate() {
-            if i < self.lines.len() {
-                self.lines.push(Line::new(Rc::clone(&self.font)));
-            }
-            self.lines[i].add_text(line);
-        }
-        let mut i : usize = self.lines.len() - 1;
-        while i > 0 && self.lines[i].is_empty() { // Remove the empty lines at the end of the file
-            self.lines.pop();
-            i -= 1;
-        }
-        self.cursor.move_to(0, 0);
+        for range in underline_buffer { self.document.style_buffer.push(StyleRange::new_underline(self.to_grapheme_range(range))) }
+        for range in bold_buffer { self.document.style_buffer.push(StyleRange::new_bold(self.to_grapheme_range(range))) }
+        self.update_substitutions_from_prefs();
+        self.update_auto_pairs_from_prefs();
+        self.update_indentation(&text);
         self.update_text_layout();
         if filepath != "new-file.txt" {
             self.add_to_recent_paths(filepath);
@@ -1032,6 +1300,53 @@ impl Editor {
 
     }
 
+    /// Read the file at `path` and remember its format
+    /// Return `None` and tell the user when the file isn't a text file, so the document is kept
+    fn read_file(&mut self, path: &Path, filepath: &str) -> Option<String> {
+        let bytes = fs::read(path).expect(&format!("Unable to load file to {}", filepath));
+        let (text, file_format) = FileFormat::decode(&bytes);
+        if !is_text(&text) {
+            let name = get_file_name(filepath);
+            self.menu.open_with(vec![MenuItem::new(&iformat!("{name} is not a text file"), MenuAction::Void)]);
+            return Option::None;
+        }
+        self.document.file_format = file_format;
+        Some(text)
+    }
+
+    fn toggle_file_format_popup(&mut self) {
+        if self.menu.is_visible { return self.menu.close(); }
+        let format = self.document.file_format;
+        let line_ending_items = [LineEnding::Lf, LineEnding::Crlf, LineEnding::Cr].iter()
+            .map(|&line_ending| MenuItem::new(&line_ending.to_string(), MenuAction::SetFileFormat(FileFormat { line_ending, ..format })))
+            .collect();
+        let encoding_items = [(Encoding::Utf8, false), (Encoding::Utf8, true), (Encoding::Utf16Le, true), (Encoding::Utf16Be, true), (Encoding::Windows1252, false)].iter()
+            .map(|&(encoding, bom)| {
+                let title = if bom { iformat!("{encoding} with BOM") } else { encoding.to_string() };
+                MenuItem::new(&title, MenuAction::SetFileFormat(FileFormat { encoding, bom, ..format }))
+            })
+            .collect();
+        self.menu.open_with(vec![
+            MenuItem::new(&iformat!("Format: {format}"), MenuAction::Information),
+            MenuItem::separator(),
+            MenuItem::new_with_submenu("Line endings >", ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone(), line_ending_items)),
+            MenuItem::new_with_submenu("Encoding >", ContextualMenu::new_with_items(self.system_font.clone(), self.event_sender.clone(), encoding_items)),
+        ]);
+    }
+
+    /// Convert the document to `file_format`, the file is written in this format on the next save
+    pub fn set_file_format(&mut self, file_format: FileFormat) {
+        self.menu.close();
+        if file_format == self.document.file_format { return; }
+        self.document.file_format = file_format;
+        self.set_dirty(true);
+    }
+
+    /// Replace the whole document
+    pub fn set_text(&mut self, text: &str) {
+        self.edit(|document| document.set_text(text));
+    }
+
     pub fn get_animations(&mut self) -> Vec<&mut Option<Animation>> {
         let mut animations = vec![
             &mut self.cursor.animation.x, &mut self.cursor.animation.y,
@@ -1039,6 +1354,14 @@ impl Editor {
             &mut self.selection.start_animation.x, &mut self.selection.start_animation.y,
             &mut self.selection.end_animation.x, &mut self.selection.end_animation.y,
         ];
+        for (cursor, selection) in &mut self.secondary_cursors {
+            animations.push(&mut cursor.animation.x);
+            animations.push(&mut cursor.animation.y);
+            animations.push(&mut selection.start_animation.x);
+            animations.push(&mut selection.start_animation.y);
+            animations.push(&mut selection.end_animation.x);
+            animations.push(&mut selection.end_animation.y);
+        }
         for animation in self.menu.get_animations() {
             animations.push(animation)
         }
@@ -1054,14 +1377,41 @@ impl Editor {
         }
     }
 
+    fn get_layout_settings(&self) -> LayoutSettings {
+        LayoutSettings {
+            font_size: self.font.borrow().size,
+            editor_width: self.font.borrow().editor_size.x,
+            wrap_column: self.get_wrap_column(),
+            use_substitutions: self.use_substitutions,
+            tab_width: self.document.indentation.width,
+        }
+    }
+
+    /// Lay out the lines changed since the last call, or all of them if the layout settings changed
     pub fn update_text_layout(&mut self) {
-        let mut difference = 0;
-        for (i, line) in (&mut self.lines).iter_mut().enumerate() {
-            let diff = line.update_text_layout(i, &self.style_buffer);
-            if i as u32 == self.cursor.y { difference = diff; }
+        let start_time = Instant::now();
+        let settings = self.get_layout_settings();
+        if settings != self.layout_settings {
+            for line in &mut self.lines { line.invalidate(); }
+            self.layout_settings = settings;
+            self.content_width = Option::None;
+        }
+        let mut rows_changed = false;
+        let mut content_width = self.content_width;
+        for (i, line) in self.lines.iter_mut().enumerate().filter(|(_, line)| line.is_dirty()) {
+            let (row_count, width) = (line.row_count(), line.get_width());
+            let text = self.document.buffer.line_text(i as u32);
+            line.update_text_layout(i, &text, self.document.alignments[i], &self.document.style_buffer, &settings);
+            rows_changed |= line.row_count() != row_count;
+            // Only the lines laid out again are measured, unless the widest one got narrower
+            let new_width = line.get_width();
+            if new_width < width && content_width.is_some_and(|content_width| width >= content_width) { content_width = Option::None; }
+            content_width = content_width.map(|content_width| content_width.max(new_width));
         }
-        self.font.borrow_mut().style_changed = false;
-        self.cursor.move_to((self.cursor.x as i32 - difference) as u32, self.cursor.y);
+        if rows_changed { self.update_row_offsets(); }
+        self.content_width = Some(content_width.unwrap_or_else(|| self.lines.iter().map(|line| line.get_width()).fold(0., f32::max)));
+        self.stats.layout_duration = start_time.elapsed();
+        if !self.is_camera_free { self.update_camera(); } // the cursor may have moved to another row
         self.update_stats();
     }
 
@@ -1085,48 +1435,57 @@ impl Editor {
         let char_width = self.font.borrow().char_width;
         let char_height = self.font.borrow().char_height;
 
-        let mut previous_line_height = 0.;
-        self.selection.render(&self.lines, &self.camera, graphics);
+        let (first_line, last_line) = self.get_visible_lines();
+        self.render_search_matches((first_line, last_line), graphics);
+        self.render_bracket_pair((first_line, last_line), graphics);
+        self.selection.render(&self.lines, &self.document.buffer, (first_line, last_line), &self.camera, graphics);
+        for (_, selection) in &self.secondary_cursors {
+            selection.render(&self.lines, &self.document.buffer, (first_line, last_line), &self.camera, graphics);
+        }
         // Draw text
-        for (i, line) in self.lines.iter().enumerate() {
+        for line in &self.lines[first_line as usize ..= last_line as usize] {
             line.render(
                 - self.camera.computed_x(),
-                - self.camera.computed_y() + previous_line_height * (i as f32),
+                - self.camera.computed_y() + line.row_offset as f32 * char_height,
                 graphics,
             );
-            previous_line_height = if line.get_unstyled_ftb().height() > 0. {
-                line.get_unstyled_ftb().height()
-            } else {
-                line.font.borrow().char_height
-            };
         }
 
-        // Specific line camera which derive of the global one to handle text alignement
-        let line_offset = self.get_current_line().alignment_offset;
-        let line_camera = Camera::from_with_offset(&self.camera, Vector2::new(-line_offset, 0.));
-
         // draw underline
-        let mut underline_buffer: Vec<Range> = self.style_buffer.iter_mut().filter(|sr| sr.underline == true).map(|sr| sr.range).collect();
+        let (visible_start, visible_end) = (Position::new(0, first_line), Position::new(self.document.buffer.line_len(last_line), last_line));
+        let mut underline_buffer: Vec<Range> = self.document.style_buffer.iter()
+            .filter(|sr| sr.underline)
+            .filter_map(|sr| sr.intersect(visible_start, visible_end)) // only the visible part of the range
+            .map(|sr| sr.range)
+            .collect();
         for range in &mut underline_buffer {
             assert!(range.is_valid());
-            let line = &self.lines[range.start.unwrap().y as usize];
-            let line_offset = line.alignment_offset;
-            let line_camera = Camera::from_with_offset(&self.camera, Vector2::new(-line_offset, 0.));
-            let lines_index = range.get_lines_index(&self.lines);
+            let lines_index = range.get_lines_index(&self.document.buffer);
             let initial_y = range.get_real_start().unwrap().y;
             for (i, (start, end)) in lines_index.iter().enumerate() {
-                let y = (initial_y as usize + i) as f32 * char_height;
-                graphics.draw_line(
-                    Vector2::new(*start as f32 * char_width - line_camera.computed_x(), y + 0.9 * char_height - line_camera.computed_y()),
-                    Vector2::new(*end as f32 * char_width - line_camera.computed_x(), y + 0.9 * char_height - line_camera.computed_y()),
-                    1.,
-                    Color::BLACK
-                );
+                let line = &self.lines[initial_y as usize + i];
+                for (position, right) in line.get_visual_spans(*start as f32 * char_width, *end as f32 * char_width) {
+                    let y = position.y + 0.9 * char_height - self.camera.computed_y();
+                    graphics.draw_line(
+                        Vector2::new(position.x - self.camera.computed_x(), y),
+                        Vector2::new(right - self.camera.computed_x(), y),
+                        1.,
+                        Color::BLACK
+                    );
+                }
             }
         }
         // self.camera._render(graphics);
-        self.cursor.render(&line_camera, graphics);
-        let menu_position = self.cursor.position() - self.camera.position() + Vector2::new(CURSOR_OFFSET_X, self.font.borrow().char_height);
+        let current_line = &self.lines[self.cursor.y as usize];
+        self.cursor.render(current_line, &self.camera, graphics);
+        for (cursor, _) in &self.secondary_cursors {
+            cursor.render(&self.lines[cursor.y as usize], &self.camera, graphics);
+        }
+        self.render_drop_position(graphics);
+        for scrollbar in self.get_scrollbars() {
+            scrollbar.render(self.scrollbar_drag.is_some_and(|(orientation, _)| orientation == scrollbar.orientation), graphics);
+        }
+        let menu_position = self.cursor.display_position(current_line) - self.camera.position() + Vector2::new(CURSOR_OFFSET_X, self.font.borrow().char_height);
         self.menu.render(menu_position, graphics);
         graphics.draw_rectangle( // draw the title bar
             Rectangle::new(
@@ -1147,3 +1506,311 @@ impl Editor {
         self.stats.draw_duration = start_time.elapsed();
     }
 }
+
+/// The name of the file at `filepath`, without its folders
+fn get_file_name(filepath: &str) -> &str {
+    Path::new(filepath).file_name().and_then(|name| name.to_str()).unwrap_or(filepath)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::{env, fs, process};
+
+    use speedy2d::dimen::Vector2;
+    use speedy2d::window::{MouseScrollDistance, VirtualKeyCode};
+
+    use crate::document::{parse_auto_pairs, DEFAULT_AUTO_PAIRS};
+    use crate::document::file_format::{FileFormat, LineEnding};
+    use crate::document::indentation::Indentation;
+    use crate::document::position::Position;
+    use crate::document::range::Range;
+    use crate::document::range_trait::RangeTrait;
+    use crate::document::style_range::StyleRange;
+    use crate::editable::Editable;
+    use crate::editor::{Editor, AUTO_SCROLL_SPEED, DEFAULT_USE_SUBSTITUTIONS, WHEEL_SCROLL_LINES};
+    use crate::line::WrapMode;
+    use crate::menu_actions::MenuAction;
+
+    /// A headless editor: no window and no event sender, so nothing is animated
+    fn editor_with_text(text: &str) -> Editor {
+        let mut editor = Editor::new(800., 600., Vector2::ZERO, 0.);
+        editor.set_text(text);
+        editor.update_text_layout();
+        editor
+    }
+
+    fn type_text(editor: &mut Editor, text: &str) {
+        for c in text.chars() { editor.add_char(c.to_string()); }
+    }
+
+    /// Where the main cursor is displayed, it follows the one of the document
+    fn cursor_position(editor: &Editor) -> Position {
+        assert_eq!(Position::new(editor.cursor.x, editor.cursor.y), editor.document.cursor);
+        editor.document.cursor
+    }
+
+    /// A path in the temp dir that no other test run uses, the caller removes the file
+    fn temp_file_path(name: &str) -> PathBuf { env::temp_dir().join(format!("text-editor-{}-{}", process::id(), name)) }
+
+    #[test]
+    fn new_line_and_join_lines() {
+        let mut editor = editor_with_text("hello world");
+        editor.move_cursor(Position::new(5, 0));
+        editor.handle_key(VirtualKeyCode::Return);
+        assert_eq!(editor.document.buffer.to_string(), "hello\n world");
+        assert_eq!(editor.lines.len(), 2);
+        assert_eq!(cursor_position(&editor), Position::new(0, 1));
+        editor.delete_char();
+        assert_eq!(editor.document.buffer.to_string(), "hello world");
+        assert_eq!(editor.lines.len(), 1);
+        assert_eq!(cursor_position(&editor), Position::new(5, 0));
+    }
+
+    #[test]
+    fn cursors_follow_the_document() {
+        let mut editor = editor_with_text("one two\nthree four");
+        editor.add_cursor(Position::new(0, 1));
+        editor.edit(|document| document.select_current_word());
+        assert_eq!(editor.secondary_cursors.len(), 1);
+        editor.cut();
+        assert_eq!(editor.clipboard, "one"); // a headless editor keeps the clipboard in memory
+        editor.paste();
+        assert_eq!(editor.document.buffer.to_string(), "one two\nonethree four");
+        assert_eq!(editor.secondary_cursors[0].0.x, 3);
+        editor.handle_key(VirtualKeyCode::Escape);
+        assert!(editor.secondary_cursors.is_empty());
+    }
+
+    #[test]
+    fn bracket_pair_follows_the_edits() {
+        let mut editor = editor_with_text("(a)");
+        editor.edit(|document| document.move_cursor(Position::ZERO));
+        assert_eq!(editor.get_bracket_pair(), Some((Position::new(0, 0), Position::new(2, 0))));
+        editor.edit(|document| { document.insert_text(Position::ZERO, "b"); });
+        assert_eq!(editor.document.cursor, Position::ZERO);
+        assert_eq!(editor.get_bracket_pair(), Option::None); // found again at the same cursor
+    }
+
+    #[test]
+    fn substitutions_are_display_only() {
+        let mut editor = editor_with_text("");
+        type_text(&mut editor, "a -> b");
+        editor.update_text_layout();
+        assert_eq!(editor.document.buffer.to_string(), "a -> b");
+        assert_eq!(cursor_position(&editor), Position::new(6, 0));
+        let char_width = editor.font.borrow().char_width;
+        assert_eq!(editor.lines[0].get_buffer_column_at(0, 3. * char_width), 4); // right after the arrow
+    }
+
+    #[test]
+    fn tabs_are_aligned_to_tab_stops() {
+        let mut editor = editor_with_text("");
+        editor.document.indentation = Indentation { use_tabs: true, width: 4 };
+        type_text(&mut editor, "ab");
+        editor.handle_key(VirtualKeyCode::Tab);
+        type_text(&mut editor, "c");
+        assert_eq!(editor.document.buffer.to_string(), "ab\tc");
+        let char_width = editor.font.borrow().char_width;
+        let line = &editor.lines[0];
+        assert_eq!(line.to_visual_position(3. * char_width).x - line.to_visual_position(0.).x, 4. * char_width); // `c` is on the next stop
+        assert_eq!(line.get_buffer_column_at(0, 2.4 * char_width), 2); // the first half of the tab
+        assert_eq!(line.get_buffer_column_at(0, 3.6 * char_width), 3);
+    }
+
+    #[test]
+    fn only_changed_lines_are_laid_out() {
+        let mut editor = editor_with_text("a\nb\nc");
+        editor.edit(|document| { document.insert_text(Position::new(1, 1), "b"); });
+        assert_eq!(editor.lines.iter().map(|line| line.is_dirty()).collect::<Vec<bool>>(), vec![false, true, false]);
+        editor.update_text_layout();
+        assert!(editor.lines.iter().all(|line| !line.is_dirty()));
+        editor.edit(|document| document.selection.set(Position::new(0, 2), Position::new(1, 2)));
+        editor.bold();
+        assert_eq!(editor.lines.iter().map(|line| line.is_dirty()).collect::<Vec<bool>>(), vec![false, false, true]);
+        let width = editor.lines[0].get_unstyled_ftb().width();
+        editor.increase_font_size();
+        assert!(editor.lines[0].get_unstyled_ftb().width() > width); // a new font size lays out every line
+    }
+
+    #[test]
+    fn visible_lines() {
+        let text = (0 .. 1000).map(|i| i.to_string()).collect::<Vec<String>>().join("\n");
+        let mut editor = editor_with_text(&text);
+        let (first, last) = editor.get_visible_lines();
+        assert_eq!(first, 0);
+        assert!((11 .. 100).contains(&last));
+        editor.move_cursor(Position::new(0, 900));
+        let (first, last) = editor.get_visible_lines();
+        assert!((801 .. 900).contains(&first) && (900 .. 1000).contains(&last));
+    }
+
+    #[test]
+    fn search_counter() {
+        let mut editor = editor_with_text("ab ab\nab");
+        editor.toggle_search_menu();
+        editor.search_for("ab");
+        assert_eq!(editor.menu.items[0].title, "Find: 1 of 3");
+        editor.find_previous_match();
+        assert_eq!(editor.menu.items[0].title, "Find: 3 of 3");
+        editor.edit(|document| { document.insert_text(Position::new(2, 1), " ab"); });
+        editor.update_search_counter();
+        assert_eq!(editor.menu.items[0].title, "Find: 3 of 4"); // the matches are found again after an edit
+        editor.edit(|document| document.selection.reset());
+        editor.update_search_counter();
+        assert_eq!(editor.menu.items[0].title, "Find: 4 matches");
+        editor.search_for("x");
+        assert_eq!(editor.menu.items[0].title, "Find: no match");
+        editor.toggle_search_menu();
+        assert!(!editor.is_search_menu_open());
+    }
+
+    #[test]
+    fn missing_prefs_use_the_defaults() {
+        let mut editor = editor_with_text("");
+        editor.cached_prefs = Some(serde_yaml::from_str("soft_wrap: true").unwrap()); // the prefs of an older version
+        editor.filepath = Some("notes.md".to_string());
+        editor.use_substitutions = !DEFAULT_USE_SUBSTITUTIONS;
+        editor.update_substitutions_from_prefs();
+        assert_eq!(editor.use_substitutions, DEFAULT_USE_SUBSTITUTIONS);
+        editor.document.auto_pairs.clear();
+        editor.update_auto_pairs_from_prefs();
+        assert_eq!(editor.document.auto_pairs, parse_auto_pairs(&DEFAULT_AUTO_PAIRS));
+    }
+
+    #[test]
+    fn multi_click_selection() {
+        let mut editor = editor_with_text("one two three\nfour");
+        editor.select_at_click(Position::new(5, 0), 2);
+        assert_eq!(editor.get_selected_text(), "two");
+        editor.extend_selection_by_unit(Position::new(9, 0));
+        assert_eq!(editor.get_selected_text(), "two three");
+        editor.extend_selection_by_unit(Position::new(1, 0)); // backward from the first word
+        assert_eq!(editor.get_selected_text(), "one two");
+        editor.select_at_click(Position::new(2, 0), 3);
+        editor.extend_selection_by_unit(Position::new(0, 1));
+        assert_eq!(editor.get_selected_text(), "one two three\nfour");
+        let char_size = Vector2::new(editor.font.borrow().char_width, editor.font.borrow().char_height);
+        editor.begin_block_selection(Position::new(1, 0));
+        editor.update_selection(Vector2::new(3.2 * char_size.x, 1.5 * char_size.y));
+        assert_eq!(editor.get_selected_text(), "ne\nou"); // not extended from the line of the triple click
+    }
+
+    #[test]
+    fn scroll_without_moving_the_cursor() {
+        let mut editor = editor_with_text(&"line\n".repeat(200));
+        editor.scroll(Vector2::new(0., 500.));
+        editor.update_text_layout();
+        assert_eq!(editor.camera.get_scroll(), Vector2::new(0., 500.)); // a layout doesn't bring the camera back to the cursor
+        assert_eq!(cursor_position(&editor), Position::ZERO);
+        editor.scroll(Vector2::new(0., 1e6));
+        let max_scroll = editor.get_content_size().y - editor.font.borrow().editor_size.y;
+        assert_eq!(editor.camera.get_scroll().y, max_scroll);
+        editor.move_cursor(Position::new(0, 1));
+        assert!(editor.camera.get_scroll().y < 500.);
+    }
+
+    #[test]
+    fn wheel_scroll_and_content_width() {
+        let mut editor = editor_with_text(&"line\n".repeat(200));
+        let (char_width, char_height) = (editor.font.borrow().char_width, editor.font.borrow().char_height);
+        editor.scroll_by_wheel(MouseScrollDistance::Lines { x: 0., y: -2., z: 0. }); // the wheel is turned towards the user
+        assert_eq!(editor.camera.get_scroll().y, 2. * WHEEL_SCROLL_LINES * char_height);
+        editor.scroll_by_wheel(MouseScrollDistance::Pixels { x: 0., y: 1e6, z: 0. });
+        assert_eq!(editor.camera.get_scroll().y, 0.);
+        editor.edit(|document| { document.insert_text(Position::new(4, 10), "wider"); });
+        editor.update_text_layout();
+        assert_eq!(editor.get_content_size().x, 9. * char_width);
+        editor.edit(|document| document.remove_text(Position::new(4, 10), Position::new(9, 10))); // the widest line gets narrower
+        editor.update_text_layout();
+        assert_eq!(editor.get_content_size().x, 4. * char_width);
+    }
+
+    #[test]
+    fn auto_scroll_while_selecting() {
+        let mut editor = editor_with_text(&"line\n".repeat(200));
+        editor.select_at_click(Position::new(2, 0), 1);
+        assert!(!editor.auto_scroll_selection(Vector2::new(10., 10.), 16.));
+        assert!(editor.auto_scroll_selection(Vector2::new(10., 700.), 16.)); // 100px under the text area
+        assert_eq!(editor.camera.get_scroll().y, 100. * 16. * AUTO_SCROLL_SPEED);
+        assert!(editor.document.selection.end().unwrap().y > 0);
+    }
+
+    #[test]
+    fn drag_and_drop_text() {
+        let mut editor = editor_with_text("one two\nthree");
+        editor.document.style_buffer.push(StyleRange::new_bold(Range::new(Position::new(1, 0), Position::new(3, 0))));
+        editor.edit(|document| document.selection.set(Position::new(0, 0), Position::new(4, 0)));
+        let char_size = Vector2::new(editor.font.borrow().char_width, editor.font.borrow().char_height);
+        assert!(editor.begin_text_drag(Vector2::new(2.5 * char_size.x, 0.5 * char_size.y)));
+        assert!(editor.drag_text(Vector2::new(5. * char_size.x, 1.5 * char_size.y)));
+        editor.drop_text();
+        editor.update_text_layout();
+        assert_eq!(editor.document.buffer.to_string(), "two\nthreeone ");
+        assert_eq!(editor.get_selected_text(), "one ");
+        assert_eq!(editor.document.style_buffer[0].range, Range::new(Position::new(6, 1), Position::new(8, 1)));
+        assert!(editor.begin_text_drag(Vector2::new(6.2 * char_size.x, 1.5 * char_size.y)));
+        editor.drop_text(); // dropped back on the selection
+        assert!(!editor.document.selection.is_valid());
+        assert_eq!(cursor_position(&editor), Position::new(6, 1));
+    }
+
+    #[test]
+    fn confirm_before_discarding_changes() {
+        let mut editor = editor_with_text("draft");
+        type_text(&mut editor, "!");
+        editor.open_file("README.md");
+        assert_eq!(editor.document.buffer.to_string(), "!draft"); // nothing is loaded before the confirmation
+        assert!(editor.menu.is_visible);
+        assert!(editor.menu.items.iter().any(|item| item.action == MenuAction::DiscardAndOpen("README.md".to_string())));
+        editor.menu.close();
+        editor.open_file("src/"); // not a file
+        assert!(!editor.menu.is_visible);
+        let mut editor = editor_with_text("draft");
+        editor.set_file_format(FileFormat { line_ending: LineEnding::Crlf, ..editor.document.file_format }); // a change outside of the history
+        editor.open_file("README.md");
+        assert!(editor.menu.is_visible);
+        let binary_path = temp_file_path("binary-file.bin");
+        fs::write(&binary_path, b"\x00\x01\x02").unwrap();
+        let mut editor = editor_with_text("draft");
+        editor.open_file(binary_path.to_str().unwrap());
+        fs::remove_file(&binary_path).unwrap();
+        assert_eq!(editor.document.buffer.to_string(), "draft"); // the binary file is not loaded
+        assert!(editor.menu.is_visible);
+    }
+
+    #[test]
+    fn saved_file_keeps_its_bytes() {
+        let prefs_path = Editor::get_file_path("./resources/prefs.yaml");
+        let prefs = fs::read(&prefs_path).unwrap(); // the recent files are written in the prefs on load
+        let path = temp_file_path("crlf-file.txt");
+        let bytes = b"one\r\ntwo\r\n".to_vec();
+        fs::write(&path, &bytes).unwrap();
+        let mut editor = editor_with_text("");
+        editor.load_file(path.to_str().unwrap());
+        fs::write(&prefs_path, prefs).unwrap();
+        assert_eq!(editor.document.buffer.to_string(), "one\ntwo"); // no empty line after the last one
+        editor.save_to_file(path.to_str().unwrap());
+        let saved = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(saved, bytes);
+    }
+
+    #[test]
+    fn soft_wrap() {
+        let mut editor = editor_with_text("");
+        editor.wrap_mode = WrapMode::Column(10);
+        editor.set_text("hello world foo\nbar");
+        editor.update_text_layout();
+        assert_eq!(editor.lines[0].row_count(), 2); // "hello " and "world foo"
+        assert_eq!(editor.lines[1].row_offset, 2);
+        editor.move_cursor(Position::new(2, 0));
+        editor.move_cursor_relative(0, 1);
+        assert_eq!(cursor_position(&editor), Position::new(8, 0)); // the second row of the first line
+        editor.move_cursor_relative(0, 1);
+        assert_eq!(cursor_position(&editor), Position::new(2, 1));
+        let char_height = editor.font.borrow().char_height;
+        assert_eq!(editor.get_position_at(Vector2::new(0., 1.5 * char_height)), Position::new(6, 0));
+    }
+}
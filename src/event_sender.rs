@@ -0,0 +1,9 @@
+use speedy2d::window::UserEventSender;
+
+use crate::EditorEvent;
+
+/// Send `event` to the window event loop
+/// A headless editor (ex: in tests) has no event loop, so the event is dropped like when the window is closed
+pub fn send_event(event_sender: &Option<UserEventSender<EditorEvent>>, event: EditorEvent) {
+    if let Some(es) = event_sender { let _ = es.send_event(event); }
+}
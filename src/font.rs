@@ -1,3 +1,5 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::rc::Rc;
@@ -9,47 +11,45 @@ const MIN_FONT_SIZE: u32 = 4;
 const MAX_FONT_SIZE: u32 = 64;
 const DEFAULT_FONT_SIZE: u32 = 16;
 
+thread_local! {
+    /// The font faces already read from the disk, by path, a face is shared by all the fonts using it
+    static FONT_FACES: RefCell<HashMap<String, S2DFont>> = RefCell::new(HashMap::new());
+}
+
+/// Load the font face at `path` once, `None` if there is no font file there
+fn load_face(path: &str) -> Option<S2DFont> {
+    FONT_FACES.with(|faces| {
+        if let Some(face) = faces.borrow().get(path) { return Some(face.clone()); }
+        let face = S2DFont::new(fs::read(path).ok()?.as_slice()).ok()?;
+        faces.borrow_mut().insert(path.to_string(), face.clone());
+        Some(face)
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct Font {
-    pub name: String,
     pub size: u32,
     pub char_width: f32,
     pub char_height: f32,
     pub editor_size: Vector2<f32>,
-    pub style_changed: bool,
     pub s2d_font: S2DFont,
+    pub bold_s2d_font: S2DFont, // the regular face when the font has no bold one
 }
 
 impl Font {
     pub fn new(path: &str, editor_width: f32, editor_height: f32) -> Self {
         let filename = Path::new(path).file_name().unwrap().to_str().unwrap();
-        let font_file_content = fs::read(path).unwrap();
-        let s2d_font = S2DFont::new(font_file_content.as_slice()).unwrap();
+        let s2d_font = load_face(path).expect("Unable to load font");
+        let bold_path = Path::new(path).with_file_name(filename.replace("Regular", "Bold"));
+        let bold_s2d_font = load_face(bold_path.to_str().unwrap()).unwrap_or_else(|| s2d_font.clone());
         let font_layout = s2d_font.layout_text("a", 2.0 * DEFAULT_FONT_SIZE as f32, TextOptions::default());
         Self {
-            name: filename.to_string(),
             size: DEFAULT_FONT_SIZE,
             char_width: font_layout.width(),
             char_height: font_layout.height(),
             editor_size: (editor_width, editor_height).into(),
-            style_changed: false,
-            s2d_font,
-        }
-    }
-
-    pub fn get_bold(&self) -> Self {
-        // TODO: rework
-        let filename = self.name.replace("Regular.ttf", "Bold.ttf");
-        let font_file_content = fs::read("./resources/font/CourierBold.ttf").expect("Unable to find bold font");
-        let s2d_font = S2DFont::new(font_file_content.as_slice()).unwrap();
-        Self {
-            name: filename.to_string(),
-            size: self.size,
-            char_width: self.char_width,
-            char_height: self.char_height,
-            editor_size: self.editor_size,
-            style_changed: false,
             s2d_font,
+            bold_s2d_font,
         }
     }
 
@@ -58,25 +58,14 @@ impl Font {
         let font_layout = self.s2d_font.layout_text("a", 2.0 * self.size as f32, TextOptions::default());
         self.char_width = font_layout.width();
         self.char_height = font_layout.height();
-        self.style_changed = true;
     }
 
-    pub fn format(&self, text: &str) -> String {
-        text
-            .replace("-->" ,"\u{2192}")
-            .replace("->" ,"\u{2192}")
-            .replace("<--" ,"\u{2190}")
-            .replace("<-" ,"\u{2190}")
-            .replace("!=" ,"\u{2260}")
-            .replace("<=" ,"\u{2264}")
-            .replace(">=" ,"\u{2265}")
+    pub fn layout_text(&self, text: &str, text_layout_options: TextOptions) -> Rc<FormattedTextBlock> {
+        self.s2d_font.layout_text(&escape(text), 2.0 * self.size as f32, text_layout_options)
     }
 
-    pub fn layout_text(&self, text: &str, text_layout_options: TextOptions) -> Rc<FormattedTextBlock> {
-        let escaped_text = self.format(text)
-            .replace('\t', " ")// Just for rendering
-            .replace(" " ,"\u{a0}");  // Just for rendering
-        self.s2d_font.layout_text(&escaped_text, 2.0 * self.size as f32, text_layout_options)
+    pub fn layout_bold_text(&self, text: &str, text_layout_options: TextOptions) -> Rc<FormattedTextBlock> {
+        self.bold_s2d_font.layout_text(&escape(text), 2.0 * self.size as f32, text_layout_options)
     }
 
     pub fn on_resize(&mut self, size: Vector2<u32>) {
@@ -86,3 +75,9 @@ impl Font {
         );
     }
 }
+
+fn escape(text: &str) -> String {
+    text
+        .replace('\t', " ")// Just for rendering
+        .replace(' ' ,"\u{a0}")  // Just for rendering
+}
@@ -16,7 +16,12 @@ use crate::{Animation, Editable, Editor, EditorEvent, FocusElement, MenuId};
 use crate::menu_actions::{MenuAction, MenuActionFn};
 use crate::animation::EasingFunction;
 use crate::camera::Camera;
+use crate::line::WrapMode;
 use crate::render_helper::draw_rounded_rectangle_with_border;
+use crate::document::position::Position;
+use crate::document::text_buffer::get_grapheme_count;
+use crate::event_sender::send_event;
+use crate::document::go_to::GoTo;
 
 pub const MIN_INPUT_WIDTH: f32 = 250.;
 pub const MAX_INPUT_WIDTH: f32 = 600.;
@@ -28,9 +33,14 @@ const ANIMATION_DURATION: f32 = 100.;
 pub enum Validator {
     File,
     Path,
+    GoTo,
     None
 }
 
+impl Validator {
+    fn suggests_paths(&self) -> bool { matches!(self, Validator::File | Validator::Path) }
+}
+
 pub struct Input {
     pub editor: Editor,
     pub menu_id: MenuId,
@@ -69,14 +79,15 @@ impl Editable for Input {
         self.update_text_layout();
     }
 
-    fn move_cursor(&mut self, position: Vector2<u32>) { self.editor.move_cursor(position) }
+    fn move_cursor(&mut self, position: Position) { self.editor.move_cursor(position) }
 
     fn move_cursor_relative(&mut self, rel_x: i32, _rel_y: i32) {
-        if self.editor.cursor.x as i32 + rel_x < 0 { return self.unfocus(); }
-        let line = &mut self.editor.lines[0];
-        if self.editor.cursor.x >= line.buffer.len() as u32 && rel_x > 0 && self.validator != Validator::None {
-            line.add_text(&self.suggestion);
-            self.editor.move_cursor_relative(self.suggestion.len() as i32, 0);
+        if self.editor.document.cursor.x as i32 + rel_x < 0 { return self.unfocus(); }
+        let line_length = self.editor.document.buffer.line_len(0);
+        if self.editor.document.cursor.x >= line_length && rel_x > 0 && self.validator.suggests_paths() {
+            let suggestion = &self.suggestion;
+            self.editor.edit(|document| { document.insert_text(Position::new(line_length, 0), suggestion); });
+            self.editor.move_cursor_relative(get_grapheme_count(&self.suggestion) as i32, 0);
             return;
         }
         self.editor.move_cursor_relative(rel_x, 0)
@@ -97,17 +108,11 @@ impl Editable for Input {
         }
     }
 
-    fn begin_selection(&mut self) { self.editor.begin_selection() }
-
-    fn end_selection(&mut self) { self.editor.end_selection() }
-
     fn update_selection(&mut self, position: Vector2<f32>) { self.editor.update_selection(position) }
 
     fn delete_selection(&mut self) { self.editor.delete_selection() }
 
-    fn get_mouse_position_index(&mut self, position: Vector2<f32>) -> Vector2<u32> { self.editor.get_mouse_position_index(position) }
-
-    fn get_valid_cursor_position(&mut self, position: Vector2<u32>) -> Vector2<u32> { self.editor.get_valid_cursor_position(position) }
+    fn get_mouse_position_index(&mut self, position: Vector2<f32>) -> Position { self.editor.get_mouse_position_index(position) }
 
     fn select_current_word(&mut self) { self.editor.select_current_word() }
 
@@ -121,14 +126,16 @@ impl Editable for Input {
 }
 
 impl Input {
-    pub fn new(menu_id: MenuId, action_fn: MenuActionFn, es: UserEventSender<EditorEvent>) -> Self {
+    pub fn new(menu_id: MenuId, action_fn: MenuActionFn, es: Option<UserEventSender<EditorEvent>>) -> Self {
         let mut editor = Editor::new(MIN_INPUT_WIDTH, 55., Vector2::ZERO, 15.); // arbitrary height
         editor.font.borrow_mut().change_font_size(-6); // Set font size to 10
         let offset = Vector2::new(0., (50. - editor.font.borrow().char_height) / 2. - 15.);
         editor.set_offset(offset);
-        editor.set_event_sender(Some(es));
+        editor.set_event_sender(es);
         editor.camera.safe_zone_size = 30.;
         editor.should_edit_file = false;
+        editor.use_substitutions = false; // paths and queries are displayed as typed
+        editor.wrap_mode = WrapMode::None; // the input scrolls horizontally
         let blank_text_layout = editor.lines[0].get_unstyled_ftb().clone();
         Self {
             editor,
@@ -154,31 +161,24 @@ impl Input {
         self.is_focus = true;
         self.editor.update_camera();
         self.set_suggestion();
-        self.editor.event_sender.as_ref().unwrap().send_event(
-            EditorEvent::Focus(FocusElement::MenuInput(self.menu_id))
-        ).unwrap()
+        send_event(&self.editor.event_sender, EditorEvent::Focus(FocusElement::MenuInput(self.menu_id)))
     }
 
     pub fn unfocus(&mut self) {
         self.set_width(0.);
         self.is_focus = false;
-        self.editor.event_sender.as_ref().unwrap().send_event(
-            EditorEvent::Focus(FocusElement::Menu(self.menu_id))
-        ).unwrap()
+        send_event(&self.editor.event_sender, EditorEvent::Focus(FocusElement::Menu(self.menu_id)))
     }
 
     fn set_width(&mut self, width: f32) {
-        let animation_width = Animation::new(self.computed_width(), width, ANIMATION_DURATION, EasingFunction::SmootherStep, self.editor.event_sender.clone().unwrap());
-        self.animation_width = Some(animation_width);
+        self.animation_width = Animation::try_new(self.computed_width(), width, ANIMATION_DURATION, EasingFunction::SmootherStep, &self.editor.event_sender);
         self.width = width;
         self.editor.camera.width = width;
     }
 
     pub fn set_placeholder(&mut self, text: &str) {
-        let line = self.editor.lines.get_mut(0).unwrap();
-        line.empty();
-        line.add_text(text);
-        self.move_cursor(Vector2::new(text.len() as u32, 0));
+        self.editor.set_text(text);
+        self.move_cursor(Position::new(get_grapheme_count(text), 0));
         self.update_text_layout();
         self.editor.camera.reset();
     }
@@ -212,8 +212,8 @@ impl Input {
             static ref PATH_REGEX: Regex = Regex::new(r"^.*/").unwrap();
             static ref LAST_WORD_REGEX: Regex = Regex::new(r#"/([\w.\-\\\\ ]*)$"#).unwrap();
         }
-        if self.validator == Validator::None { return; }
-        let input = self.editor.lines[0].get_text();
+        if !self.validator.suggests_paths() { return; }
+        let input = self.editor.document.buffer.line_text(0);
         let path_groups = PATH_REGEX.captures(&input);
         let last_word_groups = LAST_WORD_REGEX.captures(&input);
         let path: &str = if path_groups.is_some() { path_groups.unwrap().get(0).map_or("", |m| m.as_str()) } else { "/" };
@@ -244,6 +244,7 @@ impl Input {
         lazy_static! { static ref FILE_REGEX: Regex = Regex::new(r".(txt|drn)$").unwrap(); }
         match self.validator {
             Validator::File => FILE_REGEX.is_match(text),
+            Validator::GoTo => GoTo::parse(text).is_some(),
             Validator::None => true,
             _ => false,
         }
@@ -252,32 +253,23 @@ impl Input {
     fn on_insert(&mut self) {
         if self.has_error { self.has_error = false; }
         if !self.intermediate_result { return; }
-        let result = self.editor.lines.first().unwrap().get_text();
-        self.editor.event_sender.as_ref().unwrap().send_event(
-            EditorEvent::MenuItemSelected((self.action_fn)(result))
-        ).unwrap();
+        let result = self.editor.document.buffer.line_text(0);
+        send_event(&self.editor.event_sender, EditorEvent::MenuItemSelected((self.action_fn)(result)));
     }
 
     fn submit(&mut self) {
-        let result = self.editor.lines.first().unwrap().get_text();
+        let result = self.editor.document.buffer.line_text(0);
         if !self.validate(&result) {
             self.has_error = true;
             return;
         }
         let action = (self.action_fn)(result);
-        self.editor.event_sender.as_ref().unwrap().send_event(
-            EditorEvent::MenuItemSelected(action.clone())
-        ).unwrap();
+        send_event(&self.editor.event_sender, EditorEvent::MenuItemSelected(action.clone()));
         self.unfocus();
         if MenuAction::is_async(&action) {
-            self.editor.event_sender.as_ref().unwrap().send_event(
-                EditorEvent::MenuItemSelected(MenuAction::ToggleLoader(self.menu_id))
-            ).unwrap();
+            send_event(&self.editor.event_sender, EditorEvent::MenuItemSelected(MenuAction::ToggleLoader(self.menu_id)));
         } else {
-            self.editor.event_sender.as_ref().unwrap().send_event(
-                EditorEvent::MenuItemSelected(MenuAction::CloseMenu)
-            ).unwrap();
-
+            send_event(&self.editor.event_sender, EditorEvent::MenuItemSelected(MenuAction::CloseMenu));
         }
     }
 
@@ -317,7 +309,7 @@ impl Input {
         // Draw text
         let line = self.editor.lines.first().unwrap();
         let input_camera = Camera::from_with_offset(&self.editor.camera, Vector2::new(-x, -y));
-        self.editor.selection.render(&self.editor.lines, &input_camera, graphics);
+        self.editor.selection.render(&self.editor.lines, &self.editor.document.buffer, (0, 0), &input_camera, graphics); // an input has a single line
         graphics.set_clip(Some(
             Rectangle::new(
                 Vector2::new(x as i32, y as i32),
@@ -331,6 +323,6 @@ impl Input {
             &self.suggestion_test_layout
         );
         graphics.set_clip(Option::None);
-        self.editor.cursor.render(&input_camera, graphics);
+        self.editor.cursor.render(line, &input_camera, graphics);
     }
 }
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::cmp;
 use std::rc::Rc;
 use std::cell::RefCell;
 
@@ -5,14 +6,15 @@ use speedy2d::color::Color;
 use speedy2d::dimen::Vector2;
 use speedy2d::font::{FormattedTextBlock, TextAlignment, TextOptions};
 use speedy2d::Graphics2D;
-use crate::style_range::StyleRange;
+use unicode_segmentation::UnicodeSegmentation;
 
+use crate::document::{Alignment, Layout};
+use crate::document::position::Position;
+use crate::document::style_range::StyleRange;
+use crate::document::text_buffer::get_grapheme_count;
 use crate::font::Font;
-use crate::range::Range;
-use crate::range_trait::RangeTrait;
 use crate::render_helper::draw_rectangle;
-
-const INITIAL_LINE_CAPACITY: usize = 1024;
+use crate::typography::DisplayText;
 
 #[derive(Derivative)]
 #[derivative(Clone)]
@@ -32,140 +34,249 @@ impl StyleBlock {
     }
 }
 
+/// How the lines wider than the editor are displayed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    None, // a line is a single row, the camera scrolls horizontally
+    Window, // wrap at the width of the editor
+    Column(u32), // wrap at a fixed column, or at the width of the editor if it is narrower
+}
+
+/// What the layout of every line depends on, besides its text and styles
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LayoutSettings {
+    pub font_size: u32,
+    pub editor_width: f32, // the alignment offsets depend on it
+    pub wrap_column: Option<u32>,
+    pub use_substitutions: bool,
+    pub tab_width: u32,
+}
+
+/// A visual row of a line, a line has several rows when it is soft wrapped
+#[derive(Derivative)]
+#[derivative(Clone)]
+struct Row {
+    start: u32, // first display column of the row
+    end: u32,
+    alignment_offset: f32,
+    style_block: Vec<StyleBlock>, // the first element is the all row without style
+}
+
+/// The layout of a line of the document, its text is stored in the editor `TextBuffer`
 #[derive(Derivative)]
 #[derivative(Debug, Clone)]
 pub struct Line {
-    pub buffer: Vec<String>,
     pub font: Rc<RefCell<Font>>,
     pub alignment: TextAlignment,
-    pub alignment_offset: f32,
+    pub row_offset: u32, // the first row of the line in the document, set by the editor
     #[derivative(Debug = "ignore")]
-    pub style_block: Vec<StyleBlock>,
-    previous_string: String,
+    rows: Vec<Row>,
+    display_text: DisplayText,
+    is_dirty: bool, // the text or the styles of the line changed since its last layout
 }
 
 impl Line {
     pub fn new(font: Rc<RefCell<Font>>) -> Self {
         let style_block = vec![StyleBlock::new_unstyle(font.borrow().layout_text("", TextOptions::default()))];
         Line {
-            buffer: Vec::with_capacity(INITIAL_LINE_CAPACITY),
-            previous_string: String::new(),
+            display_text: DisplayText::default(),
             alignment: TextAlignment::Left,
-            alignment_offset: 0.,
-            style_block,
+            row_offset: 0,
+            rows: vec![Row { start: 0, end: 0, alignment_offset: 0., style_block }],
+            is_dirty: true,
             font,
         }
     }
 
-    pub fn add_text(&mut self, text: &str) {
-        for c in text.chars() {
-            self.buffer.push(c.to_string());
+    pub fn set_alignment(&mut self, alignment: TextAlignment) {
+        let editor_width = self.font.borrow().editor_size.x;
+        for row in &mut self.rows {
+            let width = row.style_block[0].formatted_text_block.width();
+            row.alignment_offset = match alignment {
+                TextAlignment::Left => 0.,
+                TextAlignment::Center => (editor_width - width) / 2.,
+                TextAlignment::Right => editor_width - width,
+            };
         }
+        self.alignment = alignment;
     }
 
-    /// Empty the mline buffer
-    pub fn empty(&mut self) {
-        let length = self.buffer.len();
-        self.buffer.drain(0 .. length);
+    pub fn get_unstyled_ftb(&self) -> &Rc<FormattedTextBlock> {
+        &self.rows[0].style_block[0].formatted_text_block
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.buffer.join("") == ""
-    }
+    pub fn row_count(&self) -> u32 { self.rows.len() as u32 }
 
-    pub fn set_alignment(&mut self, alignment: TextAlignment) {
-        let editor_width = self.font.borrow().editor_size.x;
-        self.alignment_offset = match alignment {
-            TextAlignment::Left => 0.,
-            TextAlignment::Center => (editor_width - self.get_unstyled_ftb().width()) / 2.,
-            TextAlignment::Right => editor_width - self.get_unstyled_ftb().width()
-        };
-        self.alignment = alignment;
+    /// The width of the widest row, with its alignment offset
+    pub fn get_width(&self) -> f32 {
+        let char_width = self.font.borrow().char_width;
+        self.rows.iter().map(|row| row.alignment_offset + (row.end - row.start) as f32 * char_width).fold(0., f32::max)
     }
 
-    pub fn get_text(&self) -> String {
-        self.buffer.join("")
-    }
+    pub fn is_dirty(&self) -> bool { self.is_dirty }
 
-    pub fn get_word_count(&self) -> u32 { self.buffer.join("").split(' ').filter(|w| *w != "").count() as u32 }
+    /// Lay out the line again on the next `update_text_layout`, the previous layout is kept until then
+    pub fn invalidate(&mut self) { self.is_dirty = true; }
 
-    pub fn get_word_at(&self, index: u32) -> (u32, u32) {
-        let mut start_index = index;
-        let mut end_index = index;
-        let chars: Vec<char> =  self.buffer.join("").chars().collect();
-        while start_index > 0 && chars[start_index as usize - 1] != ' ' {
-            start_index -= 1;
-        }
-        while end_index < chars.len() as u32 && chars[end_index as usize] != ' ' {
-            end_index += 1;
+    /// Layout the `text` of the line `y`, the typographic substitutions only change what is displayed
+    /// With a wrap column in the `settings` the line is split in rows, after the last space that fits when there is one
+    pub fn update_text_layout(&mut self, y: usize, text: &str, alignment: Alignment, style_buffer: &[StyleRange], settings: &LayoutSettings) {
+        let display_text = DisplayText::new(text, settings.use_substitutions, settings.tab_width);
+        let font = self.font.borrow();
+        let graphemes: Vec<&str> = display_text.text.graphemes(true).collect();
+        let (line_start, line_end) = (Position::new(0, y as u32), Position::new(get_grapheme_count(text), y as u32));
+        let style_parts: Vec<(usize, usize, &StyleRange)> = style_buffer.iter()
+            .filter_map(|style_range| {
+                let part = style_range.intersect(line_start, line_end)?;
+                let start = display_text.to_display_column(part.range.start.unwrap().x) as usize;
+                Some((start, display_text.to_display_column(part.range.end.unwrap().x) as usize, style_range))
+            })
+            .collect();
+        let mut rows = vec![];
+        for (row_start, row_end) in get_rows_bounds(&graphemes, settings.wrap_column) {
+            let mut style_block = vec![StyleBlock::new_unstyle(font.layout_text(&graphemes[row_start .. row_end].concat(), TextOptions::default()))];
+            for (start, end, style_range) in &style_parts {
+                let (start, end) = (*start.max(&row_start), *end.min(&row_end));
+                if start >= end { continue; }
+                let styled_text = graphemes[start .. end].concat();
+                let ftb =
+                    if style_range.bold { font.layout_bold_text(&styled_text,  TextOptions::default())}
+                    else { font.layout_text(&styled_text,  TextOptions::default())};
+                style_block.push(StyleBlock {
+                    formatted_text_block: ftb,
+                    offset: (start - row_start) as f32 * font.char_width,
+                    color: Color::from_int_rgb(style_range.color.r, style_range.color.g, style_range.color.b),
+                });
+            }
+            rows.push(Row { start: row_start as u32, end: row_end as u32, alignment_offset: 0., style_block });
         }
-        (start_index, end_index)
+        drop(font);
+        self.rows = rows;
+        self.set_alignment(alignment.into()); // the offsets depend on the width of the rows
+        self.display_text = display_text;
+        self.is_dirty = false;
     }
 
-    pub fn get_next_jump(&self, index: u32) -> (u32, u32) {
-        let mut start_index = index;
-        let mut end_index = index;
-        let char_jump_list = [' ', '_', '-', '/', '(', ')', '[', ']', '{', '}', '"', '\''];
-        let chars: Vec<char> =  self.buffer.join("").chars().collect();
-        let max_indices = chars.len() as u32;
-        while start_index > 0 &&  char_jump_list.contains(&chars[start_index as usize - 1]) { start_index -= 1 }
-        while end_index < max_indices &&  char_jump_list.contains(&chars[end_index as usize]) { end_index += 1 }
-        while start_index > 0 && !char_jump_list.contains(&chars[start_index as usize - 1]) { start_index -= 1; }
-        while end_index < max_indices && !char_jump_list.contains(&chars[end_index as usize]) { end_index += 1; }
-        (start_index, end_index)
+    /// The row showing the display column `display_x`, a column at the end of a row is shown at the start of the next one
+    fn get_row_index(&self, display_x: u32) -> usize {
+        cmp::min(self.rows.partition_point(|row| row.end <= display_x), self.rows.len() - 1)
     }
 
-    pub fn get_unstyled_ftb(&self) -> &Rc<FormattedTextBlock> {
-        &self.style_block[0].formatted_text_block
+    /// Convert an horizontal position computed from the buffer columns, ex: the cursor one,
+    /// to the position where the text is displayed in the document, alignment and wrapping included
+    pub fn to_visual_position(&self, x: f32) -> Vector2<f32> {
+        let (char_width, char_height) = (self.font.borrow().char_width, self.font.borrow().char_height);
+        let display_x = self.display_text.to_display_x(x / char_width);
+        let row_index = self.get_row_index(display_x.max(0.) as u32);
+        let row = &self.rows[row_index];
+        Vector2::new(
+            row.alignment_offset + (display_x - row.start as f32) * char_width,
+            (self.row_offset + row_index as u32) as f32 * char_height,
+        )
     }
 
-    /// return the difference of length between the raw buffer and the styled text
-    pub fn update_text_layout(&mut self, y: usize, style_buffer: &Vec<StyleRange>) -> i32 {
-        let string = self.get_text();
-        let mut font = self.font.borrow();
-        let font_formatted_string = font.format(&string);
-        let mut diff: i32 = 0;
-        if string != font_formatted_string {
-            diff = self.buffer.len() as i32;
-            self.buffer = font_formatted_string
-                .split("")
-                .map(|c| c.to_string())
-                .filter(|s| s != "")
-                .collect();
-            diff -= self.buffer.len() as i32;
-        }
-        if font_formatted_string != self.previous_string || font.style_changed {
-            // The first element is the all line without style
-            self.style_block = vec![StyleBlock::new_unstyle(font.layout_text(&font_formatted_string, TextOptions::default()))];
-            let line_range = Range::new((0, y as u32).into(), (self.buffer.len() as u32, y as u32).into());
-            let line_style_buffer: Vec<&StyleRange> = style_buffer
-                .iter()
-                .filter(|sr| line_range.include(&sr.range) || sr.range.include(&line_range))
-                .collect();
-
-            for style_range in line_style_buffer.iter() {
-                let start = if style_range.get_real_start().unwrap().y == y as u32 { style_range.get_real_start().unwrap().x as usize } else { 0 };
-                let end = if style_range.get_real_end().unwrap().y == y as u32 { style_range.get_real_end().unwrap().x as usize } else { self.buffer.len() };
-                let ftb =
-                    if style_range.bold { font.get_bold().layout_text(&font_formatted_string[start .. end],  TextOptions::default())}
-                    else { font.layout_text(&font_formatted_string[start .. end],  TextOptions::default())};
-                self.style_block.push(StyleBlock {
-                    formatted_text_block: ftb,
-                    offset: start as f32 * font.char_width,
-                    color: style_range.color,
-                });
+    /// The parts of the rows covered between two horizontal positions computed from the buffer columns
+    /// Each part is returned as its top left corner and its right side, in the document
+    pub fn get_visual_spans(&self, start: f32, end: f32) -> Vec<(Vector2<f32>, f32)> {
+        let char_width = self.font.borrow().char_width;
+        let (start_position, end_position) = (self.to_visual_position(start), self.to_visual_position(end));
+        if start_position.y == end_position.y { return vec![(start_position, end_position.x)]; }
+        let first_row = self.get_row_index(self.display_text.to_display_x(start / char_width).max(0.) as u32);
+        let last_row = self.get_row_index(self.display_text.to_display_x(end / char_width).max(0.) as u32);
+        (first_row ..= last_row).map(|i| {
+            let row = &self.rows[i];
+            let y = (self.row_offset + i as u32) as f32 * self.font.borrow().char_height;
+            let left = if i == first_row { start_position.x } else { row.alignment_offset };
+            let right = if i == last_row { end_position.x } else { row.alignment_offset + (row.end - row.start) as f32 * char_width };
+            (Vector2::new(left, y), right)
+        }).collect()
+    }
+
+    /// The buffer column displayed at the horizontal position `x` of the row `row_index` of the line
+    pub fn get_buffer_column_at(&self, row_index: u32, x: f32) -> u32 {
+        let char_width = self.font.borrow().char_width;
+        let row_index = cmp::min(row_index as usize, self.rows.len() - 1);
+        let row = &self.rows[row_index];
+        // The end of a wrapped row is also the start of the next one, so a click after it stays before its last char
+        let last_column = if row_index + 1 < self.rows.len() { cmp::max(row.end, row.start + 1) - 1 } else { row.end };
+        let column = ((x - row.alignment_offset) / char_width + 0.5).max(0.) as u32;
+        self.display_text.to_buffer_column(cmp::min(row.start + column, last_column))
+    }
+
+    pub fn render(&self, x: f32, y: f32, graphics: &mut Graphics2D) {
+        let char_height = self.font.borrow().char_height;
+        for (i, row) in self.rows.iter().enumerate() {
+            let y = y + i as f32 * char_height;
+            for sb in &row.style_block {
+                let x = x + row.alignment_offset + sb.offset;
+                let ftb = &sb.formatted_text_block;
+                // draw_rectangle(x, y, ftb.width(), ftb.height(), Color::WHITE, graphics);
+                graphics.draw_text(Vector2::new(x, y), sb.color, ftb);
             }
-            self.previous_string = font_formatted_string;
         }
-        diff
     }
+}
 
-    pub fn render(&self, x: f32, y: f32, graphics: &mut Graphics2D) {
-        for sb in &self.style_block {
-            let x = x + self.alignment_offset + sb.offset;
-            let ftb = &sb.formatted_text_block;
-            // draw_rectangle(x, y, ftb.width(), ftb.height(), Color::WHITE, graphics);
-            graphics.draw_text(Vector2::new(x, y), sb.color, ftb);
+impl From<Alignment> for TextAlignment {
+    fn from(alignment: Alignment) -> Self {
+        match alignment {
+            Alignment::Left => TextAlignment::Left,
+            Alignment::Center => TextAlignment::Center,
+            Alignment::Right => TextAlignment::Right,
+        }
+    }
+}
+
+/// The rows of the laid out lines, a cursor moved up or down stays at the same place on the screen
+impl Layout for Vec<Line> {
+    fn get_position_on_row(&self, position: Position, rel_y: i32) -> Position {
+        let line = &self[cmp::min(position.y as usize, self.len() - 1)];
+        let (char_width, char_height) = (line.font.borrow().char_width, line.font.borrow().char_height);
+        let visual_position = line.to_visual_position(position.x as f32 * char_width);
+        get_position_at(self, visual_position + Vector2::new(0., (rel_y as f32 + 0.5) * char_height))
+    }
+}
+
+/// The buffer position of the text displayed at `position` in the document
+pub fn get_position_at(lines: &[Line], position: Vector2<f32>) -> Position {
+    let row = (position.y / lines[0].font.borrow().char_height).max(0.) as u32;
+    let y = lines.partition_point(|line| line.row_offset <= row).saturating_sub(1);
+    let line = &lines[y];
+    Position::new(line.get_buffer_column_at(row - line.row_offset, position.x), y as u32)
+}
+
+/// Split a line in rows of at most `wrap_column` graphemes, the spaces at the end of a row may overflow it
+fn get_rows_bounds(graphemes: &[&str], wrap_column: Option<u32>) -> Vec<(usize, usize)> {
+    let Some(wrap_column) = wrap_column else { return vec![(0, graphemes.len())]; };
+    let wrap_column = cmp::max(wrap_column, 1) as usize;
+    let mut rows = vec![];
+    let mut start = 0;
+    while graphemes.len() - start > wrap_column {
+        let mut end = start + wrap_column;
+        while end < graphemes.len() && graphemes[end] == " " { end += 1; } // the spaces stay at the end of the row
+        if end == graphemes.len() { break; }
+        if graphemes[end - 1] != " " {
+            // Break after the last space of the row, or in the middle of a word too long to fit
+            if let Some(space_index) = graphemes[start .. end].iter().rposition(|g| *g == " ") { end = start + space_index + 1; }
         }
+        rows.push((start, end));
+        start = end;
+    }
+    rows.push((start, graphemes.len()));
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    use crate::line::get_rows_bounds;
+
+    #[test]
+    fn rows_bounds() {
+        let graphemes: Vec<&str> = "abcdefgh ij".graphemes(true).collect();
+        assert_eq!(get_rows_bounds(&graphemes, None), vec![(0, 11)]);
+        assert_eq!(get_rows_bounds(&graphemes, Some(4)), vec![(0, 4), (4, 9), (9, 11)]); // a word too long is cut
+        assert_eq!(get_rows_bounds(&graphemes, Some(10)), vec![(0, 9), (9, 11)]);
     }
 }
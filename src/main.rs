@@ -2,12 +2,12 @@
 extern crate derivative;
 extern crate core;
 
+mod document;
 mod editor;
 mod cursor;
 mod font;
 mod line;
 mod animation;
-mod range;
 mod selection;
 mod camera;
 mod contextual_menu;
@@ -18,8 +18,9 @@ mod menu_actions;
 mod stats;
 mod open_ai_wrapper;
 mod loader;
-mod style_range;
-mod range_trait;
+mod event_sender;
+mod typography;
+mod scrollbar;
 
 
 // Uncomment to load TESL parser
@@ -28,17 +29,19 @@ mod range_trait;
 use std::thread;
 use std::env;
 use std::ffi::OsStr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
 use speedy2d::color::Color;
 use speedy2d::dimen::Vector2;
-use speedy2d::window::{KeyScancode, ModifiersState, MouseButton, VirtualKeyCode, WindowCreationOptions, WindowHandler, WindowHelper, WindowPosition, WindowSize, WindowStartupInfo};
+use speedy2d::window::{KeyScancode, ModifiersState, MouseButton, MouseScrollDistance, VirtualKeyCode, WindowCreationOptions, WindowHandler, WindowHelper, WindowPosition, WindowSize, WindowStartupInfo};
 use speedy2d::{Graphics2D, Window};
 
 use ifmt::iformat;
 
 use crate::editor::Editor;
+use crate::document::go_to::split_file_position;
+use crate::document::position::Position;
 use crate::animation::Animation;
 use crate::editable::Editable;
 use crate::editor::{EDITOR_OFFSET_TOP, EDITOR_PADDING};
@@ -47,6 +50,8 @@ use crate::open_ai_wrapper::OpenAIWrapper;
 
 const FPS: u64 = 60;
 const FRAME_DURATION: u64 = 1000 / FPS; // ms
+const MULTI_CLICK_DELAY: u64 = 400; // ms
+const MULTI_CLICK_DISTANCE: f32 = 4.; // px
 
 type MenuId = [isize; 3]; // Support 3 nested menu
 
@@ -71,6 +76,9 @@ struct EditorWindowHandler {
     tick_timestamp: Instant,
     mouse_button_pressed: (bool, bool), // (Left, Right)
     mouse_position: Vector2<f32>,
+    alt_click_position: Option<Position>, // adds a cursor on release, or begins a block selection on drag
+    last_click: Option<(Instant, Vector2<f32>)>,
+    click_count: u32, // 2 for a double click, 3 for a triple click, then it starts again from 1
     focus: FocusElement,
 }
 
@@ -93,29 +101,40 @@ impl WindowHandler<EditorEvent> for EditorWindowHandler {
         match user_event {
             EditorEvent::Redraw => helper.request_redraw(),
             EditorEvent::Update => {
-                self.editor.update(self.tick_timestamp.elapsed().as_millis() as f32);
+                let dt = self.tick_timestamp.elapsed().as_millis() as f32;
+                let is_selecting = self.mouse_button_pressed.0 && self.alt_click_position.is_none() && self.focus == FocusElement::Editor;
+                if is_selecting && self.editor.auto_scroll_selection(self.mouse_position, dt) { helper.request_redraw(); }
+                self.editor.update(dt);
                 self.tick_timestamp = Instant::now();
             },
             EditorEvent::Focus(focus_element) => self.focus = focus_element,
             EditorEvent::MenuItemSelected(item) => match item {
                 MenuAction::Void => {},
                 MenuAction::Exit => helper.terminate_loop(),
-                MenuAction::CancelChip => self.editor.cancel_chip(),
-                MenuAction::Open(path) => self.editor.load_file(&path),
+                MenuAction::Open(path) => self.editor.open_file(&path),
+                MenuAction::DiscardAndOpen(path) => self.editor.discard_changes_and_open(&path),
                 MenuAction::Save(path) => self.editor.save_to_file(&path),
                 MenuAction::NewFile(path) => self.editor.new_file(&path),
                 MenuAction::Underline => self.editor.underline(),
                 MenuAction::Bold => self.editor.bold(),
                 MenuAction::Copy => self.editor.copy(),
-                MenuAction::Cut => { self.editor.copy(); self.editor.delete_selection(); },
+                MenuAction::Cut => self.editor.cut(),
                 MenuAction::Paste => self.editor.paste(),
                 MenuAction::OpenSubMenu => {},
                 MenuAction::CloseMenu => self.editor.menu.close(),
-                MenuAction::FindAndJump(text) => self.editor.find(&text),
+                MenuAction::Search(query) => self.editor.search_for(&query),
+                MenuAction::GoTo(target) => self.editor.go_to(&target),
+                MenuAction::SetReplacement(replacement) => self.editor.document.search.replacement = replacement,
+                MenuAction::FindNext => self.editor.find_next_match(),
+                MenuAction::FindPrevious => self.editor.find_previous_match(),
+                MenuAction::ReplaceMatch => self.editor.replace_match(),
+                MenuAction::ReplaceAllMatches => self.editor.replace_all_matches(),
+                MenuAction::ToggleSearchOption(option) => self.editor.toggle_search_option(option),
                 MenuAction::AICorrect => OpenAIWrapper::correct(&self.editor.get_selected_text(), &self.editor.get_focus_menu().unwrap()),
                 MenuAction::AIQuestion(question) => OpenAIWrapper::ask(&question.replace('$', &self.editor.get_selected_text()), &self.editor.get_focus_menu().unwrap()),
                 MenuAction::ToggleLoader(id) => self.editor.get_menu(id).toggle_loader(),
                 MenuAction::ReplaceSelection(string) => self.editor.add_text(&string),
+                MenuAction::SetFileFormat(file_format) => self.editor.set_file_format(file_format),
                 _ => {}
             },
             EditorEvent::MenuItemUnselected(_item, key) => self.editor.add_char(key),
@@ -141,6 +160,13 @@ impl WindowHandler<EditorEvent> for EditorWindowHandler {
 
     fn on_mouse_move(&mut self, helper: &mut WindowHelper<EditorEvent>, position: Vector2<f32>) {
         self.mouse_position = position;
+        if self.editor.drag_scrollbar(position) { return helper.request_redraw(); }
+        if self.editor.drag_text(position) { return helper.request_redraw(); }
+        if let Some(origin) = self.alt_click_position {
+            if self.editor.get_mouse_position_index(position) == origin { return; }
+            self.editor.begin_block_selection(origin);
+            self.alt_click_position = Option::None;
+        }
         if self.mouse_button_pressed.0 || self.editor.modifiers.shift() {
             self.editor.camera.safe_zone_size = 5.;
             self.editor.update_selection(position);
@@ -153,11 +179,20 @@ impl WindowHandler<EditorEvent> for EditorWindowHandler {
     fn on_mouse_button_down(&mut self, helper: &mut WindowHelper<EditorEvent>, button: MouseButton) {
         match button {
             MouseButton::Left => {
+                if self.editor.begin_scrollbar_drag(self.mouse_position) { return helper.request_redraw(); }
                 self.mouse_button_pressed.0 = true;
-                self.editor.selection.reset();
                 let index_position = self.editor.get_mouse_position_index(self.mouse_position);
-                self.editor.move_cursor(Vector2::new(index_position.x, index_position.y));
-                self.editor.begin_selection();
+                if self.editor.modifiers.alt() {
+                    self.alt_click_position = Some(index_position);
+                } else {
+                    let is_repeated = self.last_click.is_some_and(|(time, position)| {
+                        time.elapsed() < Duration::from_millis(MULTI_CLICK_DELAY) && (position - self.mouse_position).magnitude() < MULTI_CLICK_DISTANCE
+                    });
+                    self.click_count = if is_repeated { self.click_count % 3 + 1 } else { 1 };
+                    self.last_click = Some((Instant::now(), self.mouse_position));
+                    let is_text_dragged = self.click_count == 1 && self.editor.begin_text_drag(self.mouse_position);
+                    if !is_text_dragged { self.editor.select_at_click(index_position, self.click_count); }
+                }
             },
             MouseButton::Right => {
                 self.mouse_button_pressed.1 = true;
@@ -168,14 +203,32 @@ impl WindowHandler<EditorEvent> for EditorWindowHandler {
         helper.request_redraw();
     }
 
-    fn on_mouse_button_up(&mut self, _helper: &mut WindowHelper<EditorEvent>, button: MouseButton) {
+    fn on_mouse_button_up(&mut self, helper: &mut WindowHelper<EditorEvent>, button: MouseButton) {
         match button {
-            MouseButton::Left => self.mouse_button_pressed.0 = false,
+            MouseButton::Left => {
+                self.mouse_button_pressed.0 = false;
+                self.editor.end_scrollbar_drag();
+                self.editor.end_selection_drag();
+                self.editor.drop_text();
+                helper.request_redraw();
+                if let Some(position) = self.alt_click_position.take() { self.editor.add_cursor(position); }
+            },
             MouseButton::Right => self.mouse_button_pressed.1 = false,
             _ => ()
         }
     }
 
+    fn on_mouse_wheel_scroll(&mut self, helper: &mut WindowHelper<EditorEvent>, distance: MouseScrollDistance) {
+        if self.focus != FocusElement::Editor { return; }
+        self.editor.scroll_by_wheel(distance);
+        helper.request_redraw();
+    }
+
+    fn on_file_dropped(&mut self, helper: &mut WindowHelper<EditorEvent>, path: PathBuf) {
+        self.editor.open_file(&path.to_string_lossy());
+        helper.request_redraw();
+    }
+
     fn on_key_down(&mut self, helper: &mut WindowHelper<EditorEvent>, virtual_key_code: Option<VirtualKeyCode>, _scancode: KeyScancode) {
         let modifiers = self.editor.modifiers.clone();
         if let Some(keycode) = virtual_key_code {
@@ -200,21 +253,13 @@ impl WindowHandler<EditorEvent> for EditorWindowHandler {
                     input.add_char(unicode_codepoint.to_string());
                     input.update_text_layout();
                 }
-                FocusElement::Menu(id) => {
-                    // Cancel chip should disapear on keydown but the char should be added anyway
-                    // Ugly
-                    let menu = self.editor.get_menu(id);
-                    if menu.items[0].action == MenuAction::CancelChip {
-                        self.editor.add_char(unicode_codepoint.to_string());
-                        self.editor.update_text_layout();
-                    }
-                }
+                FocusElement::Menu(_) => {}
             }
             helper.request_redraw();
         }
     }
 
-    fn on_keyboard_modifiers_changed(&mut self, _helper: &mut WindowHelper<EditorEvent>, state: ModifiersState) { self.editor.modifiers = state.clone(); }
+    fn on_keyboard_modifiers_changed(&mut self, _helper: &mut WindowHelper<EditorEvent>, state: ModifiersState) { self.editor.set_modifiers(state); }
 }
 
 fn set_app_title(helper: &mut WindowHelper<EditorEvent>, path: &str) {
@@ -235,9 +280,12 @@ fn main() {
         )
     ).unwrap();
     let mut editor = Editor::new(1200., 800., Vector2::new(0., EDITOR_OFFSET_TOP), EDITOR_PADDING); // on mac dpr is 2 so the real size is 1200, 800
+    editor.update_wrap_mode_from_prefs();
     if args.len() > 1 {
-        let filename = &args[1];
+        // A file can be opened at a position, ex: `notes.txt:120:5`
+        let (filename, target) = if Path::new(&args[1]).exists() { (args[1].as_str(), Option::None) } else { split_file_position(&args[1]) };
         editor.load_file(filename);
+        if let Some(target) = target { editor.go_to_target(target); }
     }
 
     let window_handler = EditorWindowHandler {
@@ -246,6 +294,9 @@ fn main() {
         tick_timestamp: Instant::now(),
         mouse_button_pressed: (false, false),
         mouse_position: Vector2::new(0., 0.),
+        alt_click_position: Option::None,
+        last_click: Option::None,
+        click_count: 0,
         focus: FocusElement::Editor
     };
 
@@ -1,5 +1,7 @@
 use std::fmt;
 use crate::MenuId;
+use crate::document::file_format::FileFormat;
+use crate::document::search::SearchOption;
 
 pub(crate) type MenuActionFn = fn(String) -> MenuAction;
 
@@ -7,13 +9,13 @@ pub(crate) type MenuActionFn = fn(String) -> MenuAction;
 pub enum MenuAction {
     Open(String),
     OpenWithInput(String),
+    DiscardAndOpen(String),
     Save(String),
     SaveWithInput(String),
     Separator,
     Void,
     Information,
     Exit,
-    CancelChip,
     Underline,
     Copy,
     Cut,
@@ -25,13 +27,23 @@ pub enum MenuAction {
     Print(String),
     NewFile(String),
     NewFileWithInput(String),
-    FindAndJumpWithInput,
-    FindAndJump(String),
+    SearchWithInput,
+    Search(String),
+    ReplacementWithInput,
+    SetReplacement(String),
+    FindNext,
+    FindPrevious,
+    ReplaceMatch,
+    ReplaceAllMatches,
+    ToggleSearchOption(SearchOption),
+    GoToWithInput,
+    GoTo(String),
     AICorrect,
     AIQuestion(String),
     AIQuestionWithInput,
     ToggleLoader(MenuId),
-    ReplaceSelection(String)
+    ReplaceSelection(String),
+    SetFileFormat(FileFormat)
 }
 
 impl fmt::Display for MenuAction {
@@ -45,12 +57,25 @@ impl MenuAction {
             MenuAction::SaveWithInput(_) => MenuAction::Save,
             MenuAction::PrintWithInput => MenuAction::Print,
             MenuAction::NewFileWithInput(_) => MenuAction::NewFile,
-            MenuAction::FindAndJumpWithInput => MenuAction::FindAndJump,
+            MenuAction::SearchWithInput => MenuAction::Search,
+            MenuAction::ReplacementWithInput => MenuAction::SetReplacement,
+            MenuAction::GoToWithInput => MenuAction::GoTo,
             MenuAction::AIQuestionWithInput => MenuAction::AIQuestion,
             _ => MenuAction::Print
         }
     }
 
+    /// The actions of the find/replace panel can be repeated without opening it again
+    pub fn keeps_menu_open(action: &MenuAction) -> bool {
+        matches!(action,
+            MenuAction::FindNext
+            | MenuAction::FindPrevious
+            | MenuAction::ReplaceMatch
+            | MenuAction::ReplaceAllMatches
+            | MenuAction::ToggleSearchOption(_)
+        )
+    }
+
     pub fn is_async(action: &MenuAction) -> bool {
         match action {
             MenuAction::AIQuestion(_) => true,
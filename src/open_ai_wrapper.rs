@@ -84,7 +84,7 @@ impl OpenAIWrapper {
     }
 
     fn make_async_request(req: OAIRequest, menu: &ContextualMenu) {
-        let es = menu.event_sender.as_ref().unwrap().clone();
+        let Some(es) = menu.event_sender.clone() else { return; }; // the response is delivered through the window event loop
         let menu_id = menu.id;
         thread::spawn(move || {
             tokio::runtime::Builder::new_multi_thread()
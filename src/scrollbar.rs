@@ -0,0 +1,96 @@
+use speedy2d::color::Color;
+use speedy2d::dimen::Vector2;
+use speedy2d::Graphics2D;
+
+use crate::render_helper::draw_rounded_rectangle;
+
+pub const SCROLLBAR_WIDTH: f32 = 8.;
+const MIN_THUMB_LENGTH: f32 = 20.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orientation { Vertical, Horizontal }
+
+/// A scrollbar along an edge of the window, its thumb shows the part of the content seen through the camera
+/// The lengths are measured along its axis, in window coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct Scrollbar {
+    pub orientation: Orientation,
+    track_start: f32,
+    track_length: f32,
+    cross_position: f32, // the position of the bar on the other axis
+    thumb_start: f32,
+    thumb_length: f32,
+    max_scroll: f32,
+}
+
+impl Scrollbar {
+    /// `None` when all the content is visible, `scroll` is how far the content is scrolled along the axis
+    pub fn new(orientation: Orientation, track: (f32, f32), cross_position: f32, content_length: f32, visible_length: f32, scroll: f32) -> Option<Self> {
+        let (track_start, track_length) = track;
+        let max_scroll = content_length - visible_length;
+        if max_scroll <= 0. || track_length <= MIN_THUMB_LENGTH { return Option::None; }
+        let thumb_length = (track_length * visible_length / content_length).max(MIN_THUMB_LENGTH);
+        let thumb_start = track_start + (track_length - thumb_length) * (scroll / max_scroll).clamp(0., 1.);
+        Some(Self { orientation, track_start, track_length, cross_position, thumb_start, thumb_length, max_scroll })
+    }
+
+    /// The position along the axis and the one across it
+    fn split(&self, position: Vector2<f32>) -> (f32, f32) {
+        match self.orientation {
+            Orientation::Vertical => (position.y, position.x),
+            Orientation::Horizontal => (position.x, position.y),
+        }
+    }
+
+    pub fn contains(&self, position: Vector2<f32>) -> bool {
+        let (along, across) = self.split(position);
+        (self.track_start .. self.track_start + self.track_length).contains(&along)
+            && (self.cross_position .. self.cross_position + SCROLLBAR_WIDTH).contains(&across)
+    }
+
+    /// Where `position` is on the thumb from its start, `None` if it is outside of the thumb
+    pub fn get_grab_offset(&self, position: Vector2<f32>) -> Option<f32> {
+        let (along, _) = self.split(position);
+        let offset = along - self.thumb_start;
+        if self.contains(position) && (0. .. self.thumb_length).contains(&offset) { Some(offset) } else { Option::None }
+    }
+
+    /// The scroll that moves the thumb under `position` when it is grabbed at `grab_offset`
+    pub fn get_scroll(&self, position: Vector2<f32>, grab_offset: f32) -> f32 {
+        let (along, _) = self.split(position);
+        let progress = (along - grab_offset - self.track_start) / (self.track_length - self.thumb_length);
+        progress.clamp(0., 1.) * self.max_scroll
+    }
+
+    /// `1` if `position` is after the thumb, `-1` if it is before
+    pub fn get_page_direction(&self, position: Vector2<f32>) -> f32 {
+        let (along, _) = self.split(position);
+        if along < self.thumb_start { -1. } else { 1. }
+    }
+
+    pub fn render(&self, is_dragged: bool, graphics: &mut Graphics2D) {
+        let color = if is_dragged { Color::from_int_rgba(0, 0, 0, 110) } else { Color::from_int_rgba(0, 0, 0, 60) };
+        let (x, y, width, height) = match self.orientation {
+            Orientation::Vertical => (self.cross_position, self.thumb_start, SCROLLBAR_WIDTH, self.thumb_length),
+            Orientation::Horizontal => (self.thumb_start, self.cross_position, self.thumb_length, SCROLLBAR_WIDTH),
+        };
+        draw_rounded_rectangle(x, y, width, height, SCROLLBAR_WIDTH / 2., color, graphics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use speedy2d::dimen::Vector2;
+
+    use crate::scrollbar::{Orientation, Scrollbar};
+
+    #[test]
+    fn thumb_follows_the_scroll() {
+        assert!(Scrollbar::new(Orientation::Vertical, (0., 100.), 90., 50., 100., 0.).is_none()); // all the content is visible
+        let scrollbar = Scrollbar::new(Orientation::Vertical, (0., 100.), 90., 400., 100., 200.).unwrap();
+        assert_eq!(scrollbar.get_grab_offset(Vector2::new(92., 60.)), Some(10.)); // the thumb is 25px long and starts at 50px
+        assert_eq!(scrollbar.get_grab_offset(Vector2::new(92., 20.)), Option::None);
+        assert_eq!(scrollbar.get_scroll(Vector2::new(0., 85.), 10.), 300.);
+        assert_eq!(scrollbar.get_page_direction(Vector2::new(92., 20.)), -1.);
+    }
+}
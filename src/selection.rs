@@ -1,3 +1,4 @@
+use std::cmp;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -12,13 +13,22 @@ use crate::animation::EasingFunction;
 use crate::camera::Camera;
 use crate::font::Font;
 use crate::line::Line;
-use crate::range::{get_line_length, Range};
-use crate::range_trait::RangeTrait;
+use crate::document::position::Position;
+use crate::document::range::{get_line_length, Range};
+use crate::document::range_trait::RangeTrait;
+use crate::document::selection;
+use crate::document::text_buffer::TextBuffer;
 
 const ANIMATION_DURATION: f32 = 100.; // ms
 
+/// What a drag of the mouse extends the selection by, from the number of clicks that started it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SelectionUnit { Char, Word, Line }
+
+/// The display of a selection of the document, its bounds are animated when they move
 pub struct Selection {
-    range: Range,
+    range: Range, // the range of the document selection when it was last synced
+    is_block: bool, // the same columns are selected on every line between the start and the end
     font: Rc<RefCell<Font>>,
     pub event_sender: Option<UserEventSender<EditorEvent>>,
     pub start_animation: Vector2<Option<Animation>>,
@@ -29,6 +39,7 @@ impl Clone for Selection {
     fn clone(&self) -> Self {
         Self {
             range: self.range,
+            is_block: self.is_block,
             font: self.font.clone(),
             event_sender: self.event_sender.clone(),
             start_animation: Vector2::new(None, None),
@@ -41,6 +52,7 @@ impl Selection {
     pub fn new(font: Rc<RefCell<Font>>) -> Self {
        Self {
            range: Default::default(),
+           is_block: false,
            font,
            event_sender: Option::None,
            start_animation: Vector2::new(None, None),
@@ -48,63 +60,67 @@ impl Selection {
        }
     }
 
-    pub fn start(&self) -> Option<Vector2<u32>> {
+    fn start(&self) -> Option<Position> {
         self.range.get_real_start()
     }
 
-    pub fn end(&self) -> Option<Vector2<u32>> {
+    fn end(&self) -> Option<Position> {
         self.range.get_real_end()
     }
 
-    pub fn set_start(&mut self, position: Vector2<u32>) {
+    fn set_start(&mut self, position: Position) {
         let start = self.range.start;
         let char_width =  self.font.borrow().char_width;
         let char_height =  self.font.borrow().char_height;
-        let es = self.event_sender.clone().unwrap();
         if let Some(start) = start {
             self.start_animation = Vector2::new(
-                Some(Animation::new(start.x as f32 * char_width, position.x as f32 * char_width, ANIMATION_DURATION, EasingFunction::SmootherStep, es.clone())),
-                Some(Animation::new(start.y as f32 * char_height, position.y as f32 * char_height, ANIMATION_DURATION, EasingFunction::SmootherStep, es.clone()))
+                Animation::try_new(start.x as f32 * char_width, position.x as f32 * char_width, ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender),
+                Animation::try_new(start.y as f32 * char_height, position.y as f32 * char_height, ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender)
             );
         }
         self.range.start(position)
     }
 
-    pub fn set_end(&mut self, position: Vector2<u32>) {
+    fn set_end(&mut self, position: Position) {
         let end = self.range.end;
         let char_width =  self.font.borrow().char_width;
         let char_height =  self.font.borrow().char_height;
-        let es = self.event_sender.clone().unwrap();
         if let Some(end) = end {
             self.end_animation = Vector2::new(
-                Some(Animation::new(end.x as f32 * char_width, position.x as f32 * char_width, ANIMATION_DURATION, EasingFunction::SmootherStep, es.clone())),
-                Some(Animation::new(end.y as f32 * char_height, position.y as f32 * char_height, ANIMATION_DURATION, EasingFunction::SmootherStep, es.clone()))
+                Animation::try_new(end.x as f32 * char_width, position.x as f32 * char_width, ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender),
+                Animation::try_new(end.y as f32 * char_height, position.y as f32 * char_height, ANIMATION_DURATION, EasingFunction::SmootherStep, &self.event_sender)
             );
         }
         self.range.end(position)
     }
 
-    pub fn set(&mut self, start: Vector2<u32>, end: Vector2<u32>) {
-        self.set_start(start);
-        self.set_end(end);
-    }
-
-    pub fn is_valid(&self) -> bool {
+    fn is_valid(&self) -> bool {
         self.range.is_valid()
     }
 
-    pub fn add(&mut self, range: Range) {
-        self.range.add(range)
-    }
-
-    pub fn reset(&mut self) {
+    fn reset(&mut self) {
         self.range.reset();
+        self.is_block = false;
         self.start_animation = Vector2::new(None, None);
         self.end_animation = Vector2::new(None, None);
     }
 
-    pub fn get_range(&self) -> Range {
-        self.range
+    /// Follow the `selection` of the document, the bounds that moved are animated
+    pub fn sync(&mut self, selection: &selection::Selection) {
+        let range = selection.get_range();
+        if range == self.range && selection.is_block() == self.is_block { return; }
+        match (range.start, range.end) {
+            (Some(start), end) => {
+                if self.range.start != Some(start) { self.set_start(start); }
+                match end {
+                    Some(end) if self.range.end != Some(end) => self.set_end(end),
+                    None => self.range.end = Option::None,
+                    _ => {}
+                }
+                self.is_block = selection.is_block();
+            },
+            (None, _) => self.reset(),
+        }
     }
 
     fn computed_start(&self) -> Vector2<f32> {
@@ -125,46 +141,53 @@ impl Selection {
         Vector2::new(x, y)
     }
 
-    pub fn get_lines_index(&mut self, lines: &[Line]) -> Vec<(u32, u32)> {
-        self.range.get_lines_index(lines)
-    }
-
-    fn get_lines_bounds(&self, lines: &[Line]) -> Vec<(f32, f32)> {
-        if !self.is_valid() { return vec![]; }
-        let font_width = self.font.borrow().char_width;
-        let start = self.start().unwrap();
-        let end = self.end().unwrap();
-        let mut result = vec![];
-        for y in start.y..=end.y {
-            if y == start.y {
-                if start.y == end.y { result.push((self.computed_start().x, self.computed_end().x)) }
-                else { result.push((self.computed_start().x, get_line_length(y, lines) as f32 * font_width))
-                }
-            } else if y == end.y { result.push((0., self.computed_end().x)) }
-            else { result.push((0., get_line_length(y, lines) as f32 * font_width)) }
-        }
-        result
+    /// The animated position of a corner of the selection
+    fn computed_corner(&self, position: Position, animation: &Vector2<Option<Animation>>) -> Vector2<f32> {
+        let x = if let Some(animation) = &animation.x { animation.value } else { position.x as f32 * self.font.borrow().char_width };
+        let y = if let Some(animation) = &animation.y { animation.value } else { position.y as f32 * self.font.borrow().char_height };
+        Vector2::new(x, y)
     }
 
-    pub fn render(&self, lines: &[Line], camera: &Camera, graphics: &mut Graphics2D) {
-        if !self.is_valid() { return; }
+    /// Draw the part of the `line` between two horizontal positions computed from the buffer columns, on every row it spans
+    fn render_line_part(&self, line: &Line, start: f32, end: f32, y_delta: f32, camera: &Camera, graphics: &mut Graphics2D) {
         let font_height = self.font.borrow().char_height;
-        let initial_y = self.start().unwrap().y as f32 * font_height - camera.computed_y();
-        let lines_bounds = self.get_lines_bounds(lines);
-        for (i, bounds) in lines_bounds.iter().enumerate() { // TODO: cache ?
-            let mut line_y = initial_y + i as f32 * font_height;
-            let line: &Line = &lines[self.start().unwrap().y as usize + i];
-            let line_offset = line.alignment_offset;
-            let line_camera = Camera::from_with_offset(camera, Vector2::new(-line_offset, 0.));
-            if i == 0 { line_y = self.computed_start().y - camera.computed_y() }
-            if i + 1 == lines_bounds.len() { line_y = self.computed_end().y - camera.computed_y() }
+        for (position, right) in line.get_visual_spans(start, end) {
+            let y = position.y + y_delta - camera.computed_y();
             graphics.draw_rectangle(
                 Rectangle::new(
-                    Vector2::new(bounds.0 - line_camera.computed_x(), line_y),
-                    Vector2::new(bounds.1 - line_camera.computed_x(), line_y + font_height),
+                    Vector2::new(position.x - camera.computed_x(), y),
+                    Vector2::new(right - camera.computed_x(), y + font_height),
                 ),
                 Color::from_int_rgba(235, 235, 235, 255),
             )
         }
     }
+
+    /// Draw a block selection as a rectangle, even over the lines shorter than its columns
+    fn render_block(&self, lines: &[Line], visible_lines: (u32, u32), camera: &Camera, graphics: &mut Graphics2D) {
+        let start = self.computed_corner(self.range.start.unwrap(), &self.start_animation);
+        let end = self.computed_corner(self.range.end.unwrap(), &self.end_animation);
+        let left = start.x.min(end.x);
+        let right = start.x.max(end.x).max(left + 2.); // a block without any column is still visible
+        for y in cmp::max(self.start().unwrap().y, visible_lines.0) ..= cmp::min(self.end().unwrap().y, visible_lines.1) {
+            self.render_line_part(&lines[y as usize], left, right, 0., camera, graphics);
+        }
+    }
+
+    /// Draw the selection on the lines between the first and last `visible_lines` only
+    pub fn render(&self, lines: &[Line], buffer: &TextBuffer, visible_lines: (u32, u32), camera: &Camera, graphics: &mut Graphics2D) {
+        if !self.is_valid() { return; }
+        if self.is_block { return self.render_block(lines, visible_lines, camera, graphics); }
+        let (font_width, font_height) = (self.font.borrow().char_width, self.font.borrow().char_height);
+        let (start, end) = (self.start().unwrap(), self.end().unwrap());
+        for y in cmp::max(start.y, visible_lines.0) ..= cmp::min(end.y, visible_lines.1) {
+            let left = if y == start.y { self.computed_start().x } else { 0. };
+            let right = if y == end.y { self.computed_end().x } else { get_line_length(y, buffer) as f32 * font_width };
+            // The first and last lines follow the vertical animation of the selection
+            let mut y_delta = 0.;
+            if y == start.y { y_delta = self.computed_start().y - start.y as f32 * font_height }
+            if y == end.y { y_delta = self.computed_end().y - end.y as f32 * font_height }
+            self.render_line_part(&lines[y as usize], left, right, y_delta, camera, graphics);
+        }
+    }
 }
\ No newline at end of file
@@ -3,5 +3,6 @@ use std::time::Duration;
 #[derive(Debug, Clone, Default)]
 pub struct Stats {
     pub update_duration: Duration,
+    pub layout_duration: Duration, // the last text layout, only the changed lines are laid out
     pub draw_duration: Duration
 }
\ No newline at end of file
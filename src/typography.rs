@@ -0,0 +1,129 @@
+use std::cmp;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Typographic substitutions, the longest patterns first so `-->` is not read as `-` + `->`
+const SUBSTITUTIONS: [(&str, &str); 7] = [
+    ("-->", "\u{2192}"),
+    ("<--", "\u{2190}"),
+    ("->", "\u{2192}"),
+    ("<-", "\u{2190}"),
+    ("!=", "\u{2260}"),
+    ("<=", "\u{2264}"),
+    (">=", "\u{2265}"),
+];
+
+/// The text of a line as it is displayed, the buffer is never modified by the substitutions
+/// Positions in the buffer and on screen are both expressed in grapheme columns
+#[derive(Debug, Clone, Default)]
+pub struct DisplayText {
+    pub text: String,
+    columns: Vec<u32>, // the display column of each buffer column, including the end of the line
+}
+
+impl DisplayText {
+    /// A tab is displayed as the spaces up to the next tab stop, every `tab_width` columns
+    pub fn new(text: &str, use_substitutions: bool, tab_width: u32) -> Self {
+        let graphemes: Vec<&str> = text.graphemes(true).collect();
+        let mut display_text = String::with_capacity(text.len());
+        let mut columns = Vec::with_capacity(graphemes.len() + 1);
+        let mut x = 0;
+        let mut display_column = 0;
+        while x < graphemes.len() {
+            let substitution = if use_substitutions { find_substitution(&graphemes[x..]) } else { Option::None };
+            columns.push(display_column);
+            match substitution {
+                Some((length, replacement)) => {
+                    display_text.push_str(replacement);
+                    display_column += 1; // a replacement is a single grapheme
+                    // A position inside the pattern is displayed after its replacement
+                    columns.resize(columns.len() + length - 1, display_column);
+                    x += length;
+                }
+                None if graphemes[x] == "\t" => {
+                    let tab_width = cmp::max(tab_width, 1);
+                    let spaces = tab_width - display_column % tab_width;
+                    display_text.push_str(&" ".repeat(spaces as usize));
+                    display_column += spaces;
+                    x += 1;
+                }
+                None => {
+                    display_text.push_str(graphemes[x]);
+                    display_column += 1;
+                    x += 1;
+                }
+            }
+        }
+        columns.push(display_column);
+        Self { text: display_text, columns }
+    }
+
+    fn end_column(&self) -> u32 { *self.columns.last().unwrap_or(&0) }
+
+    /// Display column of the buffer column `x`, past the end of the line the columns keep their width
+    pub fn to_display_column(&self, x: u32) -> u32 {
+        match self.columns.get(x as usize) {
+            Some(column) => *column,
+            None => self.end_column() + x - (self.columns.len() as u32).saturating_sub(1),
+        }
+    }
+
+    /// Buffer column displayed the closest to the column `display_x`, clamped to the line
+    pub fn to_buffer_column(&self, display_x: u32) -> u32 {
+        // The last buffer column shown at this place, so a click right after a replacement lands after its pattern
+        let index = cmp::max(self.columns.partition_point(|column| *column <= display_x), 1) - 1;
+        // A click in the second half of a tab lands after it
+        match self.columns.get(index + 1) {
+            Some(next) if next - display_x < display_x - self.columns[index] => index as u32 + 1,
+            _ => index as u32,
+        }
+    }
+
+    /// Same as `to_display_column` for a fractional column, ex: while the cursor is animated
+    pub fn to_display_x(&self, x: f32) -> f32 {
+        let column = x.max(0.).floor();
+        let start = self.to_display_column(column as u32) as f32;
+        let end = self.to_display_column(column as u32 + 1) as f32;
+        start + (end - start) * (x - column)
+    }
+}
+
+/// The length in graphemes of the pattern at the start of `graphemes`, and its replacement
+fn find_substitution(graphemes: &[&str]) -> Option<(usize, &'static str)> {
+    SUBSTITUTIONS.iter().find_map(|(pattern, replacement)| {
+        let length = pattern.len(); // the patterns are made of ASCII chars only
+        let is_matching = graphemes.len() >= length && graphemes[.. length].concat() == *pattern;
+        if is_matching { Some((length, *replacement)) } else { Option::None }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typography::DisplayText;
+
+    #[test]
+    fn substitutions_map_columns() {
+        let display_text = DisplayText::new("a -> b --> c", true, 4);
+        assert_eq!(display_text.text, "a \u{2192} b \u{2192} c");
+        assert_eq!(display_text.to_display_column(2), 2);
+        assert_eq!(display_text.to_display_column(3), 3); // inside the arrow
+        assert_eq!(display_text.to_display_column(5), 4);
+        assert_eq!(display_text.to_display_column(12), 9);
+        assert_eq!(display_text.to_display_column(14), 11); // past the end of the line
+        assert_eq!(display_text.to_buffer_column(3), 4);
+        assert_eq!(display_text.to_buffer_column(4), 5);
+        assert_eq!(display_text.to_buffer_column(20), 12);
+        assert_eq!(DisplayText::new("a -> b", false, 4).text, "a -> b");
+    }
+
+    #[test]
+    fn tabs_go_to_the_next_stop() {
+        let display_text = DisplayText::new("\tab\tc", false, 4);
+        assert_eq!(display_text.text, "    ab  c");
+        assert_eq!(display_text.to_display_column(1), 4);
+        assert_eq!(display_text.to_display_column(4), 8);
+        assert_eq!(display_text.to_buffer_column(1), 0);
+        assert_eq!(display_text.to_buffer_column(3), 1);
+        assert_eq!(display_text.to_buffer_column(8), 4);
+    }
+}